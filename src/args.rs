@@ -0,0 +1,77 @@
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::bot::Weights;
+use crate::game::garbage::Holes;
+use crate::game::random::Randomizer;
+
+pub fn parse_number<T: FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("{} expects a number", arg))
+}
+
+/// Parses a range of seeds like `0..1000`.
+pub fn parse_seeds(value: Option<String>) -> Result<Range<u64>, String> {
+    let error = || "--seeds expects a range like 0..1000".to_string();
+    let value = value.ok_or_else(error)?;
+    let (start, end) = value.split_once("..").ok_or_else(error)?;
+    let start = start.parse().map_err(|_| error())?;
+    let end = end.parse().map_err(|_| error())?;
+    Ok(start..end)
+}
+
+/// Parses the five heuristic weights, in the order they're listed in `Weights`.
+pub fn parse_weights(arg: &str, value: &str) -> Result<Weights, String> {
+    let weights = value.split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|_| format!("{} has a weight which isn't a number: {:?}", arg, v.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match weights[..] {
+        [aggregate_height, holes, bumpiness, wells, lines] => Ok(Weights { aggregate_height, holes, bumpiness, wells, lines }),
+        _ => Err(format!("{} expects five comma separated weights", arg)),
+    }
+}
+
+pub fn parse_randomizer(value: Option<String>) -> Result<Randomizer, String> {
+    match value.as_deref() {
+        Some("bag") => Ok(Randomizer::SevenBag),
+        Some("random") => Ok(Randomizer::Random),
+        _ => Err("--randomizer expects bag or random".to_string()),
+    }
+}
+
+pub fn parse_holes(value: Option<String>) -> Result<Holes, String> {
+    match value.as_deref() {
+        Some("clean") => Ok(Holes::Clean),
+        Some("messy") => Ok(Holes::Messy),
+        Some("cheese") => Ok(Holes::Cheese),
+        _ => Err("--garbage expects clean, messy or cheese".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_are_a_half_open_range() {
+        assert_eq!(parse_seeds(Some("5..20".to_string())), Ok(5..20));
+        assert!(parse_seeds(Some("5".to_string())).is_err());
+        assert!(parse_seeds(Some("5..x".to_string())).is_err());
+        assert!(parse_seeds(None).is_err());
+    }
+
+    #[test]
+    fn weights_need_all_five() {
+        let weights = parse_weights("--weights", "-0.5, -0.35,-0.18,0,0.76").unwrap();
+        assert_eq!(weights.holes, -0.35);
+        assert_eq!(weights.lines, 0.76);
+        assert!(parse_weights("--weights", "1,2,3,4").is_err());
+        assert!(parse_weights("--weights", "1,2,3,4,5,6").is_err());
+
+        // a bad entry is named rather than skipped over, which would shift the rest along
+        let err = parse_weights("--weights", "1,2,x,3,4,5").err().unwrap();
+        assert!(err.contains("\"x\""), "{}", err);
+        assert!(parse_weights("--weights", "1,2,x,3,4").is_err());
+    }
+}
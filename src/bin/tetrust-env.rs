@@ -0,0 +1,60 @@
+use std::env;
+use std::io::{self, stdin, stdout};
+use std::process;
+use std::time::Duration;
+
+use tetrust::args::{parse_number, parse_randomizer};
+use tetrust::env::{serve, ActionSpace, Env};
+use tetrust::game::engine::Rules;
+
+const USAGE: &str = "usage: tetrust-env [--actions key|placement] [--frame MS] [--max-pieces N]
+                   [--randomizer bag|random] [--no-hold]";
+
+struct Options {
+    rules: Rules,
+    action_space: ActionSpace,
+    frame: Duration,
+    max_pieces: Option<usize>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        rules: Rules::default(),
+        action_space: ActionSpace::Keys,
+        frame: Duration::from_millis(50),
+        max_pieces: None,
+    };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--actions" => options.action_space = match args.next().as_deref() {
+                Some("key") => ActionSpace::Keys,
+                Some("placement") => ActionSpace::Placements,
+                _ => return Err("--actions expects key or placement".to_string()),
+            },
+            "--frame" => options.frame = match parse_number(&arg, args.next())? {
+                0 => return Err("--frame expects at least 1 millisecond".to_string()),
+                ms => Duration::from_millis(ms),
+            },
+            "--max-pieces" => options.max_pieces = Some(parse_number(&arg, args.next())?),
+            "--randomizer" => options.rules.randomizer = parse_randomizer(args.next())?,
+            "--no-hold" => options.rules.hold = false,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn main() -> io::Result<()> {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let mut env = Env::new(options.rules, options.action_space);
+    env.set_frame(options.frame);
+    env.set_max_pieces(options.max_pieces);
+    serve(&mut env, stdin().lock(), stdout().lock())
+}
@@ -0,0 +1,37 @@
+use std::env;
+use std::process;
+
+use tetrust::relay::Server;
+
+const USAGE: &str = "usage: tetrust-server [--listen ADDR]";
+
+fn parse_args() -> Result<String, String> {
+    let mut listen = "127.0.0.1:7878".to_string();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => listen = args.next().ok_or("--listen expects an address")?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(listen)
+}
+
+fn main() {
+    let listen = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let mut server = Server::bind(&listen).unwrap_or_else(|err| {
+        eprintln!("couldn't listen on {}: {}", listen, err);
+        process::exit(1);
+    });
+    if let Ok(addr) = server.local_addr() {
+        println!("listening on {}", addr);
+    }
+
+    server.run();
+}
@@ -0,0 +1,149 @@
+use std::env;
+use std::io;
+use std::ops::Range;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+use tetrust::args::{parse_number, parse_randomizer, parse_seeds, parse_weights};
+use tetrust::bot::{Bot, ExternalBot, Heuristic, Weights};
+use tetrust::game::engine::Rules;
+use tetrust::sim::{self, Outcome, Summary};
+
+const USAGE: &str = "usage: tetrust-sim [--seeds START..END] [--max-pieces N] [--threads N] [--randomizer bag|random]
+                   [--no-hold] [--weights HEIGHT,HOLES,BUMPINESS,WELLS,LINES | --bot COMMAND]
+                   [--format json|csv] [--per-game]";
+
+struct Options {
+    seeds: Range<u64>,
+    max_pieces: usize,
+    threads: usize,
+    rules: Rules,
+    weights: Weights,
+    bot: Option<String>,
+    csv: bool,
+    per_game: bool,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    summary: Summary,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    games: Option<&'a [Outcome]>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        seeds: 0..100,
+        max_pieces: 1000,
+        threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        rules: Rules::default(),
+        weights: Weights::default(),
+        bot: None,
+        csv: false,
+        per_game: false,
+    };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seeds" => options.seeds = parse_seeds(args.next())?,
+            "--max-pieces" => options.max_pieces = parse_number(&arg, args.next())?,
+            "--threads" => options.threads = parse_number::<usize>(&arg, args.next())?.max(1),
+            "--randomizer" => options.rules.randomizer = parse_randomizer(args.next())?,
+            "--no-hold" => options.rules.hold = false,
+            "--weights" => options.weights = parse_weights(&arg, &args.next().unwrap_or_default())?,
+            "--bot" => options.bot = Some(args.next().ok_or("--bot expects a command to run")?),
+            "--format" => options.csv = match args.next().as_deref() {
+                Some("json") => false,
+                Some("csv") => true,
+                _ => return Err("--format expects json or csv".to_string()),
+            },
+            "--per-game" => options.per_game = true,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn launch(options: &Options) -> io::Result<Box<dyn Bot>> {
+    match &options.bot {
+        Some(command) => Ok(Box::new(ExternalBot::launch(command)?)),
+        None => Ok(Box::new(Heuristic::new(options.weights))),
+    }
+}
+
+fn simulate(options: &Options) -> Result<Vec<Outcome>, String> {
+    let rules = sim::headless(options.rules);
+    let next_seed = AtomicU64::new(options.seeds.start);
+    let outcomes = Mutex::new(Vec::new());
+
+    // every thread has its own bot and takes the next seed until there are none left
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads)
+            .map(|_| scope.spawn(|| -> Result<(), String> {
+                let mut bot = launch(options).map_err(|err| format!("couldn't start the bot: {}", err))?;
+                loop {
+                    let seed = next_seed.fetch_add(1, Ordering::Relaxed);
+                    if seed >= options.seeds.end {
+                        return Ok(());
+                    }
+
+                    // a game the bot crashed in says nothing about it, the other threads stop too
+                    let outcome = sim::play(bot.as_mut(), rules, seed, options.max_pieces).inspect_err(|_| {
+                        next_seed.store(options.seeds.end, Ordering::Relaxed);
+                    })?;
+                    outcomes.lock().unwrap().push(outcome);
+                }
+            }))
+            .collect();
+
+        workers.into_iter().try_for_each(|worker| worker.join().unwrap())
+    })?;
+
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|outcome| outcome.seed);
+    Ok(outcomes)
+}
+
+fn print_csv(summary: &Summary, outcomes: &[Outcome], per_game: bool) {
+    if per_game {
+        println!("seed,pieces,lines,score,topped_out,seconds,pieces_per_second");
+        for o in outcomes {
+            println!("{},{},{},{},{},{:.3},{:.2}", o.seed, o.pieces, o.lines, o.score, o.topped_out, o.seconds, o.pieces_per_second());
+        }
+        return;
+    }
+
+    println!("games,mean_pieces,mean_lines,mean_score,min_score,p25_score,median_score,p75_score,max_score,top_out_rate,pieces_per_second");
+    println!(
+        "{},{:.2},{:.2},{:.2},{},{},{},{},{},{:.4},{:.2}",
+        summary.games, summary.mean_pieces, summary.mean_lines, summary.mean_score,
+        summary.min_score, summary.p25_score, summary.median_score, summary.p75_score, summary.max_score,
+        summary.top_out_rate, summary.pieces_per_second,
+    );
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let outcomes = simulate(&options).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let summary = Summary::new(&outcomes);
+
+    if options.csv {
+        print_csv(&summary, &outcomes, options.per_game);
+    } else {
+        let report = Report { summary, games: if options.per_game { Some(&outcomes) } else { None } };
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    }
+}
@@ -0,0 +1,244 @@
+use std::env;
+use std::io;
+use std::ops::Range;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+use tetrust::args::{parse_holes, parse_number, parse_randomizer, parse_seeds, parse_weights};
+use tetrust::bot::{Bot, ExternalBot, Heuristic, Weights};
+use tetrust::game::engine::Rules;
+use tetrust::sim::{self, Duel, Standing};
+
+const USAGE: &str = "usage: tetrust-tournament [--heuristic NAME[=HEIGHT,HOLES,BUMPINESS,WELLS,LINES]].. [--bot NAME=COMMAND]..
+                          [--seeds START..END] [--max-pieces N] [--threads N] [--randomizer bag|random]
+                          [--garbage clean|messy|cheese] [--no-hold] [--format json|csv] [--per-game]";
+
+enum Contestant {
+    Heuristic(Weights),
+    External(String),
+}
+
+struct Options {
+    names: Vec<String>,
+    contestants: Vec<Contestant>,
+    seeds: Range<u64>,
+    max_pieces: usize,
+    threads: usize,
+    rules: Rules,
+    csv: bool,
+    per_game: bool,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    standings: Vec<Standing>,
+    matchups: Vec<Matchup>,
+    mean_game_pieces: f64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    games: Option<Vec<Game<'a>>>,
+}
+
+// how two of the bots did against each other
+#[derive(Serialize)]
+struct Matchup {
+    bots: [String; 2],
+    wins: [usize; 2],
+    draws: usize,
+}
+
+#[derive(Serialize)]
+struct Game<'a> {
+    bots: [&'a str; 2],
+
+    #[serde(flatten)]
+    duel: &'a Duel,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        names: Vec::new(),
+        contestants: Vec::new(),
+        seeds: 0..20,
+        max_pieces: 500,
+        threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        rules: Rules::default(),
+        csv: false,
+        per_game: false,
+    };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--heuristic" => {
+                let value = args.next().ok_or("--heuristic expects a name")?;
+                let (name, weights) = match value.split_once('=') {
+                    Some((name, weights)) => (name.to_string(), parse_weights(&arg, weights)?),
+                    None => (value, Weights::default()),
+                };
+                options.names.push(name);
+                options.contestants.push(Contestant::Heuristic(weights));
+            }
+            "--bot" => {
+                let value = args.next().unwrap_or_default();
+                let (name, command) = value.split_once('=').ok_or("--bot expects NAME=COMMAND")?;
+                options.names.push(name.to_string());
+                options.contestants.push(Contestant::External(command.to_string()));
+            }
+            "--seeds" => options.seeds = parse_seeds(args.next())?,
+            "--max-pieces" => options.max_pieces = parse_number(&arg, args.next())?,
+            "--threads" => options.threads = parse_number::<usize>(&arg, args.next())?.max(1),
+            "--randomizer" => options.rules.randomizer = parse_randomizer(args.next())?,
+            "--garbage" => options.rules.garbage = parse_holes(args.next())?,
+            "--no-hold" => options.rules.hold = false,
+            "--format" => options.csv = match args.next().as_deref() {
+                Some("json") => false,
+                Some("csv") => true,
+                _ => return Err("--format expects json or csv".to_string()),
+            },
+            "--per-game" => options.per_game = true,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    if options.contestants.len() < 2 {
+        return Err("a tournament needs at least two bots".to_string());
+    }
+    Ok(options)
+}
+
+fn launch(contestant: &Contestant) -> io::Result<Box<dyn Bot>> {
+    match contestant {
+        Contestant::Heuristic(weights) => Ok(Box::new(Heuristic::new(*weights))),
+        Contestant::External(command) => Ok(Box::new(ExternalBot::launch(command)?)),
+    }
+}
+
+// every pair of bots plays every seed once, both get the same pieces so there's no need to
+// swap sides
+fn play(options: &Options) -> Result<Vec<([usize; 2], Duel)>, String> {
+    let rules = sim::headless(options.rules);
+    let count = options.contestants.len();
+    let pairs: Vec<[usize; 2]> = (0..count)
+        .flat_map(|a| (a + 1..count).map(move |b| [a, b]))
+        .collect();
+    let jobs: Vec<([usize; 2], u64)> = pairs.iter()
+        .flat_map(|pair| options.seeds.clone().map(move |seed| (*pair, seed)))
+        .collect();
+
+    let next_job = AtomicUsize::new(0);
+    let duels = Mutex::new(Vec::new());
+
+    // every thread starts its own bots when it first needs them
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.threads)
+            .map(|_| scope.spawn(|| -> Result<(), String> {
+                let mut bots: Vec<Option<Box<dyn Bot>>> = (0..count).map(|_| None).collect();
+                loop {
+                    let (pair, seed) = match jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) {
+                        Some(job) => *job,
+                        None => return Ok(()),
+                    };
+                    for &i in &pair {
+                        if bots[i].is_none() {
+                            bots[i] = Some(launch(&options.contestants[i])
+                                .map_err(|err| format!("couldn't start {}: {}", options.names[i], err))?);
+                        }
+                    }
+
+                    let (first, second) = bots.split_at_mut(pair[1]);
+                    let first = first[pair[0]].as_deref_mut().unwrap();
+                    let second = second[0].as_deref_mut().unwrap();
+
+                    // a crash isn't a fair loss, the tournament stops instead of counting it
+                    let duel = sim::duel([first, second], rules, seed, options.max_pieces).map_err(|err| {
+                        next_job.store(jobs.len(), Ordering::Relaxed);
+                        format!("{} against {} on seed {}: {}", options.names[pair[0]], options.names[pair[1]], seed, err)
+                    })?;
+                    duels.lock().unwrap().push((pair, duel));
+                }
+            }))
+            .collect();
+
+        workers.into_iter().try_for_each(|worker| worker.join().unwrap())
+    })?;
+
+    let mut duels = duels.into_inner().unwrap();
+    duels.sort_by_key(|(pair, duel)| (*pair, duel.seed));
+    Ok(duels)
+}
+
+fn matchups(names: &[String], duels: &[([usize; 2], Duel)]) -> Vec<Matchup> {
+    let mut matchups: Vec<([usize; 2], Matchup)> = Vec::new();
+    for (pair, duel) in duels {
+        let index = match matchups.iter().position(|(played, _)| played == pair) {
+            Some(index) => index,
+            None => {
+                let bots = [names[pair[0]].clone(), names[pair[1]].clone()];
+                matchups.push((*pair, Matchup { bots, wins: [0, 0], draws: 0 }));
+                matchups.len() - 1
+            }
+        };
+
+        let matchup = &mut matchups[index].1;
+        match duel.winner {
+            Some(winner) => matchup.wins[winner] += 1,
+            None => matchup.draws += 1,
+        }
+    }
+
+    matchups.into_iter().map(|(_, matchup)| matchup).collect()
+}
+
+fn print_csv(options: &Options, standings: &[Standing], duels: &[([usize; 2], Duel)]) {
+    if options.per_game {
+        println!("bot_a,bot_b,seed,winner,pieces_a,pieces_b,attack_a,attack_b,seconds");
+        for (pair, duel) in duels {
+            let winner = duel.winner.map(|side| options.names[pair[side]].as_str()).unwrap_or("");
+            println!(
+                "{},{},{},{},{},{},{},{},{:.3}",
+                options.names[pair[0]], options.names[pair[1]], duel.seed, winner,
+                duel.pieces[0], duel.pieces[1], duel.attack[0], duel.attack[1], duel.seconds,
+            );
+        }
+        return;
+    }
+
+    println!("bot,games,wins,losses,draws,win_rate,attack_per_piece,mean_pieces");
+    for s in standings {
+        println!("{},{},{},{},{},{:.4},{:.3},{:.2}", s.name, s.games, s.wins, s.losses, s.draws, s.win_rate, s.attack_per_piece, s.mean_pieces);
+    }
+}
+
+fn main() {
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let duels = play(&options).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let standings = Standing::table(&options.names, &duels);
+
+    if options.csv {
+        print_csv(&options, &standings, &duels);
+        return;
+    }
+
+    // a game lasts until the longer side stops
+    let lengths: usize = duels.iter().map(|(_, duel)| duel.pieces[0].max(duel.pieces[1])).sum();
+    let report = Report {
+        standings,
+        matchups: matchups(&options.names, &duels),
+        mean_game_pieces: if duels.is_empty() { 0.0 } else { lengths as f64 / duels.len() as f64 },
+        games: options.per_game.then(|| duels.iter()
+            .map(|(pair, duel)| Game { bots: [&options.names[pair[0]], &options.names[pair[1]]], duel })
+            .collect()),
+    };
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
@@ -0,0 +1,124 @@
+use crate::bot::{Bot, Placement};
+use crate::game::engine::{Board, Engine, BOARD_HEIGHT};
+use crate::game::movegen::engine_placements;
+
+/// How much each feature of a board counts towards its evaluation, higher evaluations are better.
+#[derive(Copy, Clone)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            aggregate_height: -0.510066,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            wells: -0.1,
+            lines: 0.760666,
+        }
+    }
+}
+
+/// Greedy bot which tries every placement of the current and held piece and takes the best evaluated board.
+#[derive(Default)]
+pub struct Heuristic {
+    pub weights: Weights,
+}
+
+impl Heuristic {
+    pub fn new(weights: Weights) -> Self {
+        Heuristic { weights }
+    }
+
+    pub fn evaluate(&self, board: &Board, lines: usize) -> f64 {
+        let heights = column_heights(board);
+
+        let aggregate_height: usize = heights.iter().sum();
+        let bumpiness: usize = heights.windows(2)
+            .map(|pair| (pair[0] as isize - pair[1] as isize).unsigned_abs())
+            .sum();
+
+        // a well is a column lower than both of its neighbours, the walls count as full height
+        let mut wells = 0;
+        for x in 0..heights.len() {
+            let left = if x == 0 { BOARD_HEIGHT as usize } else { heights[x - 1] };
+            let right = heights.get(x + 1).copied().unwrap_or(BOARD_HEIGHT as usize);
+            let depth = left.min(right).saturating_sub(heights[x]);
+            wells += depth;
+        }
+
+        self.weights.aggregate_height * aggregate_height as f64
+            + self.weights.holes * holes(board, &heights) as f64
+            + self.weights.bumpiness * bumpiness as f64
+            + self.weights.wells * wells as f64
+            + self.weights.lines * lines as f64
+    }
+
+    /// The best of the given placements on the board, if there are any.
+    pub fn best(&self, board: &Board, placements: Vec<Placement>) -> Option<Placement> {
+        let mut best: Option<(f64, Placement)> = None;
+
+        for placement in placements {
+            let (after, lines) = placement.apply(board);
+            let evaluation = self.evaluate(&after, lines);
+
+            match &best {
+                Some((best_evaluation, _)) if *best_evaluation >= evaluation => {}
+                _ => best = Some((evaluation, placement)),
+            }
+        }
+
+        best.map(|(_, placement)| placement)
+    }
+}
+
+impl Bot for Heuristic {
+    fn suggest(&mut self, engine: &Engine) -> Option<Placement> {
+        self.best(engine.board(), engine_placements(engine))
+    }
+}
+
+// every column the board has, co-op boards are wider
+fn column_heights(board: &Board) -> Vec<usize> {
+    (0..board.width())
+        .map(|x| {
+            (0..BOARD_HEIGHT as usize)
+                .find(|&y| board.is_occupied(x, y))
+                .map(|y| BOARD_HEIGHT as usize - y)
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+// empty cells with something above them in the same column
+fn holes(board: &Board, heights: &[usize]) -> usize {
+    heights.iter()
+        .enumerate()
+        .map(|(x, height)| {
+            (BOARD_HEIGHT as usize - height..BOARD_HEIGHT as usize)
+                .filter(|&y| !board.is_occupied(x, y))
+                .count()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::{Block, GARBAGE_COLOR};
+
+    #[test]
+    fn a_wide_board_is_evaluated_all_the_way_across() {
+        let mut board = Board::with_width(20);
+        board.set(15, BOARD_HEIGHT as usize - 2, Block::Occupied(GARBAGE_COLOR));
+
+        let holes = Weights { aggregate_height: 0.0, holes: 1.0, bumpiness: 0.0, wells: 0.0, lines: 0.0 };
+        assert_eq!(column_heights(&board).len(), 20);
+        assert_eq!(Heuristic::new(holes).evaluate(&board, 0), 1.0);
+    }
+}
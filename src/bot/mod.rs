@@ -0,0 +1,19 @@
+use crate::game::engine::Engine;
+
+mod heuristic;
+mod tbp;
+
+pub use crate::game::movegen::Placement;
+pub use heuristic::{Heuristic, Weights};
+pub use tbp::ExternalBot;
+
+/// Something which can play the game, given the engine it decides where the current piece goes.
+pub trait Bot {
+    /// None when there's no piece to place or nowhere to put it.
+    fn suggest(&mut self, engine: &Engine) -> Option<Placement>;
+
+    /// Why the bot can't play on, a bot which has failed doesn't suggest anything more.
+    fn failure(&self) -> Option<&str> {
+        None
+    }
+}
@@ -0,0 +1,373 @@
+// Frontend side of the Tetris Bot Protocol (https://github.com/tetris-bot-protocol/tbp-spec),
+// the bot runs as a child process and we talk to it with one JSON message a line.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{Bot, Placement};
+use crate::game::engine::{Block, Board, Engine, BOARD_HEIGHT, BOARD_WIDTH};
+use crate::game::movegen::{cells_of, engine_placements};
+use crate::game::tetrimino::{Tetrimino, Type};
+
+// tbp boards are 40 rows tall, everything above our board is empty
+const TBP_BOARD_HEIGHT: usize = 40;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage {
+    Rules {},
+    Start {
+        hold: Option<Type>,
+        queue: Vec<Type>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<char>>>,
+    },
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece { piece: Type },
+    Stop,
+    Quit,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info { name: String },
+    Ready,
+    Error { reason: String },
+    Suggestion { moves: Vec<Move> },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Move {
+    location: Location,
+    #[serde(default)]
+    spin: Option<String>,
+}
+
+// x and y are the piece's center of rotation, y counts up from the bottom row
+#[derive(Serialize, Deserialize, Clone)]
+struct Location {
+    #[serde(rename = "type")]
+    ttype: Type,
+    orientation: Orientation,
+    x: isize,
+    y: isize,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// A bot running in another process which speaks TBP, e.g. Cold Clear.
+pub struct ExternalBot {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+
+    // what the bot thinks the game looks like, when the engine doesn't match it anymore
+    // (e.g. the bot wasn't the one playing) the bot is restarted with the engine's state
+    started: bool,
+    queue: VecDeque<Type>,
+    hold: Option<Type>,
+    expected_board: Option<Board>,
+
+    // the first error talking to the bot, it isn't asked anything after that
+    failure: Option<String>,
+}
+
+impl ExternalBot {
+    /// Runs the command with the shell and waits until the bot is ready to play.
+    pub fn launch(command: &str) -> io::Result<Self> {
+        let mut process = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+
+        let mut bot = ExternalBot {
+            name: String::new(),
+            process,
+            stdin,
+            stdout,
+            started: false,
+            queue: VecDeque::new(),
+            hold: None,
+            expected_board: None,
+            failure: None,
+        };
+
+        match bot.receive()? {
+            BotMessage::Info { name } => bot.name = name,
+            _ => return Err(protocol_error("expected info from the bot")),
+        }
+
+        bot.send(&FrontendMessage::Rules {})?;
+        match bot.receive()? {
+            BotMessage::Ready => Ok(bot),
+            BotMessage::Error { reason } => Err(protocol_error(&format!("bot refused the rules: {}", reason))),
+            _ => Err(protocol_error("expected ready from the bot")),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let line = serde_json::to_string(message)?;
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<BotMessage> {
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "bot exited"));
+            }
+
+            // messages this frontend doesn't know about are skipped as the spec asks
+            match serde_json::from_str(&line)? {
+                BotMessage::Unknown => continue,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    // tells the bot about newly revealed pieces, or restarts it when it's out of sync
+    fn sync(&mut self, engine: &Engine, current: Type) -> io::Result<()> {
+        let visible: Vec<Type> = Some(current).into_iter().chain(engine.next_type()).collect();
+        let known = self.queue.len();
+
+        let in_sync = self.started
+            && self.hold == engine.held_type()
+            && self.expected_board.as_ref() == Some(engine.board())
+            && known <= visible.len()
+            && self.queue.iter().zip(visible.iter()).all(|(a, b)| a == b);
+
+        if in_sync {
+            for &piece in &visible[known..] {
+                self.send(&FrontendMessage::NewPiece { piece })?;
+                self.queue.push_back(piece);
+            }
+            return Ok(());
+        }
+
+        if self.started {
+            self.send(&FrontendMessage::Stop)?;
+        }
+
+        self.send(&FrontendMessage::Start {
+            hold: engine.held_type(),
+            queue: visible.to_vec(),
+            combo: (engine.combo() + 1) as u32,
+            back_to_back: engine.back_to_back(),
+            board: tbp_board(engine.board()),
+        })?;
+
+        self.started = true;
+        self.queue = visible.iter().copied().collect();
+        self.hold = engine.held_type();
+        Ok(())
+    }
+
+    fn request_suggestion(&mut self, engine: &Engine) -> io::Result<Option<Placement>> {
+        let current = match engine.active() {
+            Some(active) => active.tetrimino.ttype,
+            None => return Ok(None),
+        };
+
+        self.sync(engine, current)?;
+        self.send(&FrontendMessage::Suggest)?;
+
+        let moves = match self.receive()? {
+            BotMessage::Suggestion { moves } => moves,
+            _ => return Err(protocol_error("expected a suggestion from the bot")),
+        };
+
+        // the moves are in order of preference, take the first one we can actually get to
+        for mv in moves {
+            if let Some(placement) = find_placement(engine, &mv.location) {
+                self.send(&FrontendMessage::Play { mv: mv.clone() })?;
+                self.played(mv.location.ttype);
+                self.expected_board = Some(placement.apply(engine.board()).0);
+                return Ok(Some(placement));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // keeps track of the queue and hold the same way the bot does after a move
+    fn played(&mut self, ttype: Type) {
+        let front = self.queue.pop_front();
+        if front == Some(ttype) {
+            return;
+        }
+
+        match self.hold.replace(front.unwrap()) {
+            Some(_) => {}
+
+            // holding into an empty hold brings out the next piece, which is the one played
+            None => {
+                self.queue.pop_front();
+            }
+        }
+    }
+}
+
+impl Bot for ExternalBot {
+    fn suggest(&mut self, engine: &Engine) -> Option<Placement> {
+        if self.failure.is_some() {
+            return None;
+        }
+
+        match self.request_suggestion(engine) {
+            Ok(placement) => placement,
+            Err(err) => {
+                self.failure = Some(format!("the bot {} stopped playing: {}", self.name, err));
+                None
+            }
+        }
+    }
+
+    fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        // bots get asked nicely first, but one which doesn't listen still has to go
+        let _ = self.send(&FrontendMessage::Quit);
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn tbp_board(board: &Board) -> Vec<Vec<Option<char>>> {
+    (0..TBP_BOARD_HEIGHT)
+        .map(|y| {
+            (0..BOARD_WIDTH as usize)
+                .map(|x| {
+                    if y >= BOARD_HEIGHT as usize {
+                        return None;
+                    }
+
+                    match board.block(x, BOARD_HEIGHT as usize - 1 - y) {
+                        Block::Free => None,
+                        Block::Occupied(rgb) => Some(Tetrimino::type_of_color(rgb).map_or('G', Type::letter)),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// the placement of the current or held piece which takes up the cells at the location
+fn find_placement(engine: &Engine, location: &Location) -> Option<Placement> {
+    let state = match location.orientation {
+        Orientation::North => 0,
+        Orientation::East => 1,
+        Orientation::South => 2,
+        Orientation::West => 3,
+    };
+
+    // the center of rotation within our 4x4 blocks
+    let (center_x, center_y) = match (location.ttype, location.orientation) {
+        (Type::I, Orientation::North) => (1, 1),
+        (Type::I, Orientation::East) => (2, 1),
+        (Type::I, Orientation::South) => (2, 2),
+        (Type::I, Orientation::West) => (1, 2),
+        (Type::O, Orientation::North) => (1, 1),
+        (Type::O, Orientation::East) => (1, 0),
+        (Type::O, Orientation::South) => (2, 0),
+        (Type::O, Orientation::West) => (2, 1),
+        _ => (1, 1),
+    };
+
+    let tetrimino = Tetrimino { ttype: location.ttype, state };
+    let x = location.x - center_x;
+    let y = BOARD_HEIGHT as isize - 1 - location.y - center_y;
+    let mut target = cells_of(&tetrimino, x, y);
+    target.sort_unstable();
+
+    engine_placements(engine).into_iter()
+        .find(|placement| placement.tetrimino.ttype == location.ttype && placement.cells() == target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::Rules;
+    use crate::game::puzzle::{Goal, Puzzle};
+
+    const BOTTOM: usize = BOARD_HEIGHT as usize - 1;
+
+    fn engine(queue: &str) -> Engine {
+        let puzzle = Puzzle {
+            name: "tbp".to_string(),
+            board: vec!["XXXXXXXX..".to_string()],
+            queue: queue.to_string(),
+            hold: None,
+            goal: Goal::PerfectClear,
+        };
+        Engine::with_puzzle(Rules::default(), &puzzle).unwrap()
+    }
+
+    fn cells(engine: &Engine, ttype: Type, orientation: Orientation, x: isize, y: isize) -> Option<Vec<(usize, usize)>> {
+        find_placement(engine, &Location { ttype, orientation, x, y }).map(|placement| placement.cells())
+    }
+
+    #[test]
+    fn the_board_is_sent_from_the_bottom_row_up() {
+        let board = tbp_board(engine("T").board());
+        assert_eq!(board.len(), TBP_BOARD_HEIGHT);
+        assert_eq!(board[0], [Some('G'), Some('G'), Some('G'), Some('G'), Some('G'), Some('G'), Some('G'), Some('G'), None, None]);
+        assert!(board[1..].iter().flatten().all(Option::is_none));
+    }
+
+    #[test]
+    fn locations_are_read_from_the_center_of_rotation() {
+        let engine = engine("TI");
+        assert_eq!(cells(&engine, Type::T, Orientation::North, 4, 1), Some(vec![(3, BOTTOM - 1), (4, BOTTOM - 2), (4, BOTTOM - 1), (5, BOTTOM - 1)]));
+        assert_eq!(cells(&engine, Type::T, Orientation::South, 4, 2), Some(vec![(3, BOTTOM - 2), (4, BOTTOM - 2), (4, BOTTOM - 1), (5, BOTTOM - 2)]));
+
+        // the next piece can be played by holding
+        assert_eq!(cells(&engine, Type::I, Orientation::North, 4, 1), Some(vec![(3, BOTTOM - 1), (4, BOTTOM - 1), (5, BOTTOM - 1), (6, BOTTOM - 1)]));
+        assert_eq!(cells(&engine, Type::I, Orientation::East, 8, 2), Some(vec![(8, BOTTOM - 3), (8, BOTTOM - 2), (8, BOTTOM - 1), (8, BOTTOM)]));
+    }
+
+    #[test]
+    fn a_location_nothing_can_get_to_is_not_played() {
+        let engine = engine("TI");
+        assert_eq!(cells(&engine, Type::T, Orientation::North, 4, 0), None);
+        assert_eq!(cells(&engine, Type::O, Orientation::North, 8, 0), None);
+        assert_eq!(cells(&engine, Type::T, Orientation::North, 4, 5), None);
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::fmt::Write as _;
+use std::str;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use termion::{clear, cursor, style};
+
+use crate::game::controls::Controls;
+use crate::game::engine::Mode;
+use crate::game::replay::{Ghost, Replay};
+use crate::game::view::{View, VIEW_HEIGHT, VIEW_WIDTH};
+
+/// How long the last frame stays up at the end of a replay's cast.
+const HOLD: Duration = Duration::from_secs(3);
+
+/// An asciicast v2 recording, the format asciinema plays and its web player embeds.
+pub struct Cast {
+    file: BufWriter<File>,
+}
+
+#[derive(Serialize)]
+struct Header {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+    title: &'static str,
+}
+
+impl Cast {
+    pub fn create<P: AsRef<Path>>(path: P, width: u16, height: u16) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+        let header = Header { version: 2, width, height, timestamp, title: "tetrust" };
+        serde_json::to_writer(&mut file, &header)?;
+        file.write_all(b"\n")?;
+        Ok(Cast { file })
+    }
+
+    /// Notes what was written to the terminal this long into the recording.
+    pub fn output(&mut self, time: Duration, data: &str) -> io::Result<()> {
+        let seconds = time.as_micros() as f64 / 1_000_000.0;
+        serde_json::to_writer(&mut self.file, &(seconds, "o", data))?;
+        self.file.write_all(b"\n")
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A terminal which keeps a cast of everything written to it, each flush is an event timed
+/// from when it was made. Without a cast it only passes the writes on.
+pub struct Recorder<W: Write> {
+    inner: W,
+    cast: Option<Cast>,
+    start: Instant,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(inner: W, cast: Option<Cast>) -> Self {
+        Recorder { inner, cast, start: Instant::now(), pending: Vec::new() }
+    }
+
+    // a character split across writes waits for the rest of it
+    fn record(&mut self) -> io::Result<()> {
+        let cast = match &mut self.cast {
+            Some(cast) => cast,
+            None => return Ok(()),
+        };
+
+        let valid = match str::from_utf8(&self.pending) {
+            Ok(data) => data.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        if valid > 0 {
+            let data = str::from_utf8(&self.pending[..valid]).unwrap();
+            cast.output(self.start.elapsed(), data)?;
+            self.pending.drain(..valid);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Recorder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if self.cast.is_some() {
+            self.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.record()
+    }
+}
+
+// whatever was written after the last flush, leaving the game, still goes in
+impl<W: Write> Drop for Recorder<W> {
+    fn drop(&mut self) {
+        self.record().ok();
+        if let Some(cast) = self.cast.take() {
+            cast.finish().ok();
+        }
+    }
+}
+
+/// Plays the replay again and draws it into a cast frame by frame, as the game drew it
+/// and at the times it happened.
+pub fn render(replay: Replay, path: &Path) -> Result<(), String> {
+    // dig boards start with rows the replay doesn't have, and puzzles aren't seeded
+    if !matches!(replay.mode, Mode::Marathon | Mode::Sprint | Mode::Ultra) {
+        return Err(format!("a {:?} replay can't be played again", replay.mode).to_lowercase());
+    }
+
+    let frame = Duration::from_millis(replay.frame.max(1));
+    let end = Duration::from_millis(replay.finish.map(|finish| finish.time)
+        .or_else(|| replay.inputs.last().map(|event| event.at))
+        .unwrap_or(0));
+
+    let failed = |err: io::Error| format!("couldn't write the cast {}: {}", path.display(), err);
+    let mut cast = Cast::create(path, VIEW_WIDTH, VIEW_HEIGHT).map_err(failed)?;
+    let mut ghost = Ghost::new(replay);
+    let mut view = View::new(1, 1, Controls::single());
+    // races are kept as best runs, those are played without hints
+    if !matches!(ghost.replay().mode, Mode::Sprint | Mode::Ultra) {
+        view.set_hint_key(Some("h"));
+    }
+
+    let start = format!("{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide);
+    cast.output(Duration::from_secs(0), &start).map_err(failed)?;
+
+    // the view draws everything every frame, the cast only gets the cells which changed
+    let mut screen = Screen::new(VIEW_WIDTH, VIEW_HEIGHT);
+    let mut out = Vec::new();
+    loop {
+        out.clear();
+        view.draw(&mut out, ghost.engine()).map_err(failed)?;
+        let played = ghost.engine().played();
+        let mut next = screen.clone();
+        next.apply(&String::from_utf8_lossy(&out));
+        let changes = next.changes(&screen);
+        if !changes.is_empty() {
+            cast.output(played, &changes).map_err(failed)?;
+        }
+        screen = next;
+
+        if ghost.engine().is_over() || played >= end {
+            cast.output(played + HOLD, "").map_err(failed)?;
+            break;
+        }
+        ghost.advance(played + frame);
+    }
+
+    cast.finish().map_err(failed)
+}
+
+/// What a terminal would be showing, as far as the escapes the view draws with go.
+#[derive(Clone)]
+struct Screen {
+    width: usize,
+    cells: Vec<Cell>,
+    x: usize,
+    y: usize,
+    // the graphic renditions set since the last reset, one of each kind
+    style: Vec<String>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Cell {
+    ch: char,
+    style: String,
+}
+
+impl Screen {
+    fn new(width: u16, height: u16) -> Self {
+        let blank = Cell { ch: ' ', style: String::new() };
+        Screen { width: width as usize, cells: vec![blank; width as usize * height as usize], x: 0, y: 0, style: Vec::new() }
+    }
+
+    fn apply(&mut self, data: &str) {
+        let mut chars = data.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\x1b' {
+                let index = self.y * self.width + self.x;
+                if self.x < self.width && index < self.cells.len() {
+                    self.cells[index] = Cell { ch, style: self.style.concat() };
+                }
+                self.x += 1;
+                continue;
+            }
+
+            if chars.next() != Some('[') {
+                continue;
+            }
+            let mut params = String::new();
+            let command = loop {
+                match chars.next() {
+                    Some(c) if ('@'..='~').contains(&c) => break c,
+                    Some(c) => params.push(c),
+                    None => return,
+                }
+            };
+            match command {
+                'H' => {
+                    let mut position = params.split(';').map(|n| n.parse::<usize>().unwrap_or(1).max(1) - 1);
+                    self.y = position.next().unwrap_or(0);
+                    self.x = position.next().unwrap_or(0);
+                }
+                'm' if params.is_empty() || params == "0" => self.style.clear(),
+                'm' => {
+                    let kind = params.split(';').next().unwrap_or_default();
+                    self.style.retain(|sgr| sgr[2..].split([';', 'm']).next() != Some(kind));
+                    self.style.push(format!("\x1b[{}m", params));
+                }
+                'J' if params == "2" => {
+                    let blank = Cell { ch: ' ', style: String::new() };
+                    self.cells.iter_mut().for_each(|cell| *cell = blank.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The escapes which take a terminal showing `before` to showing this.
+    fn changes(&self, before: &Screen) -> String {
+        let mut out = String::new();
+        let mut cursor = None;
+        let mut style = None;
+        for (index, (cell, old)) in self.cells.iter().zip(&before.cells).enumerate() {
+            if cell == old {
+                continue;
+            }
+
+            if cursor != Some(index) {
+                write!(out, "{}", cursor::Goto((index % self.width) as u16 + 1, (index / self.width) as u16 + 1)).unwrap();
+            }
+            if style != Some(&cell.style) {
+                write!(out, "{}{}", style::Reset, cell.style).unwrap();
+                style = Some(&cell.style);
+            }
+            out.push(cell.ch);
+            // a cell starting the next row still gets its own goto
+            cursor = Some(index + 1).filter(|next| next % self.width != 0);
+        }
+        out
+    }
+}
@@ -0,0 +1,359 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Block, Engine, Input, Mode, Rules};
+use crate::game::movegen::{engine_placements, Placement};
+use crate::game::tetrimino::Type;
+
+/// What an agent chooses between on every step.
+#[derive(Copy, Clone, PartialEq)]
+pub enum ActionSpace {
+    /// One key per step, time moves on by a frame after every key.
+    Keys,
+
+    /// One reachable placement of the current or held piece per step, picked by its index
+    /// in the observation's list of placements.
+    Placements,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Key(Input),
+
+    /// Lets a frame go by without pressing anything.
+    Wait,
+    Placement(usize),
+}
+
+#[derive(Serialize)]
+pub struct Observation {
+    /// Occupancy from the top row down, 1 where there's a block.
+    pub board: Vec<Vec<u8>>,
+    pub current: Option<Piece>,
+    pub hold: Option<Type>,
+    pub can_hold: bool,
+    pub queue: Vec<Type>,
+
+    /// Only in the placement action space, the index of one of these is the action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placements: Option<Vec<Piece>>,
+}
+
+#[derive(Serialize)]
+pub struct Piece {
+    #[serde(rename = "type")]
+    pub ttype: Type,
+    pub x: isize,
+    pub y: isize,
+
+    /// 0 is the spawn orientation, counting clockwise.
+    pub rotation: usize,
+
+    /// Whether the piece comes out of hold, only for placements.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hold: bool,
+}
+
+#[derive(Serialize)]
+pub struct Info {
+    /// Lines cleared by this step.
+    pub lines_cleared: usize,
+    pub score: usize,
+    pub lines: usize,
+    pub pieces: usize,
+    pub combo: isize,
+    pub back_to_back: bool,
+    pub topped_out: bool,
+}
+
+#[derive(Serialize)]
+pub struct Step {
+    pub observation: Observation,
+
+    /// The points scored by this step.
+    pub reward: f64,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// Gym style environment around the engine, for training agents.
+pub struct Env {
+    rules: Rules,
+    action_space: ActionSpace,
+
+    /// How much time passes for every step in the key action space.
+    frame: Duration,
+
+    /// Games are cut short after this many pieces, none means they go on until they top out.
+    max_pieces: Option<usize>,
+    engine: Engine,
+    placements: Vec<Placement>,
+    stuck: bool,
+}
+
+impl Env {
+    pub fn new(rules: Rules, action_space: ActionSpace) -> Self {
+        let mut env = Env {
+            rules,
+            action_space,
+            frame: Duration::from_millis(50),
+            max_pieces: None,
+            engine: Engine::new(rules),
+            placements: Vec::new(),
+            stuck: false,
+        };
+        env.reset(rand::random());
+        env
+    }
+
+    /// Frames are at least a millisecond, time has to move for the delays to be waited out.
+    pub fn set_frame(&mut self, frame: Duration) {
+        self.frame = frame.max(Duration::from_millis(1));
+    }
+
+    pub fn set_max_pieces(&mut self, max_pieces: Option<usize>) {
+        self.max_pieces = max_pieces;
+    }
+
+    pub fn action_space(&self) -> ActionSpace {
+        self.action_space
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Starts a new game, the same seed deals the same pieces.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.engine = Engine::with_seed(self.rules, Mode::Marathon, seed);
+        self.stuck = false;
+        self.wait_for_piece();
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> Result<Step, String> {
+        if self.is_done() {
+            return Err("the game is over, reset to play again".to_string());
+        }
+
+        let score = self.engine.stats().score;
+        let lines = self.engine.stats().lines;
+
+        match (self.action_space, action) {
+            (ActionSpace::Keys, Action::Key(input)) => {
+                self.engine.input(input);
+                self.engine.tick(self.frame);
+            }
+            (ActionSpace::Keys, Action::Wait) => self.engine.tick(self.frame),
+            (ActionSpace::Placements, Action::Placement(index)) => {
+                let placement = self.placements.get(index)
+                    .ok_or_else(|| format!("there's no placement {}, there are {}", index, self.placements.len()))?
+                    .clone();
+                for input in placement.inputs {
+                    self.engine.input(input);
+                }
+                self.wait_for_piece();
+            }
+            (ActionSpace::Keys, _) => return Err("expected a key or wait action".to_string()),
+            (ActionSpace::Placements, _) => return Err("expected a placement action".to_string()),
+        }
+
+        let stats = self.engine.stats();
+        let info = Info {
+            lines_cleared: stats.lines - lines,
+            score: stats.score,
+            lines: stats.lines,
+            pieces: stats.pieces,
+            combo: self.engine.combo(),
+            back_to_back: self.engine.back_to_back(),
+            topped_out: self.engine.is_lost() || self.stuck,
+        };
+
+        Ok(Step {
+            reward: (stats.score - score) as f64,
+            done: self.is_done(),
+            observation: self.observe(),
+            info,
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        let cut_short = self.max_pieces.is_some_and(|max| self.engine.stats().pieces >= max);
+        self.engine.is_lost() || self.stuck || cut_short
+    }
+
+    pub fn observe(&self) -> Observation {
+        let board = self.engine.board().blocks()
+            .map(|row| row.iter().map(|block| matches!(block, Block::Occupied(_)) as u8).collect())
+            .collect();
+
+        let current = self.engine.active().map(|active| Piece {
+            ttype: active.tetrimino.ttype,
+            x: active.x,
+            y: active.y,
+            rotation: active.tetrimino.state,
+            hold: false,
+        });
+
+        let placements = match self.action_space {
+            ActionSpace::Keys => None,
+            ActionSpace::Placements => Some(self.placements.iter()
+                .map(|placement| Piece {
+                    ttype: placement.tetrimino.ttype,
+                    x: placement.x,
+                    y: placement.y,
+                    rotation: placement.tetrimino.state,
+                    hold: placement.hold,
+                })
+                .collect()),
+        };
+
+        Observation {
+            board,
+            current,
+            hold: self.engine.held_type(),
+            can_hold: self.engine.can_hold(),
+            queue: self.engine.next_type().into_iter().collect(),
+            placements,
+        }
+    }
+
+    // placements are only picked while a piece is falling, so the clear and entry delays are waited out
+    fn wait_for_piece(&mut self) {
+        if self.action_space != ActionSpace::Placements {
+            return;
+        }
+
+        while !self.engine.is_lost() && self.engine.active().is_none() {
+            self.engine.tick(self.frame);
+        }
+
+        self.placements = engine_placements(&self.engine);
+        self.stuck = !self.engine.is_lost() && self.placements.is_empty();
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Request {
+    Reset {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Step(Action),
+    Close,
+}
+
+#[derive(Serialize)]
+struct Error {
+    error: String,
+}
+
+/// Serves the environment as JSON lines, one request per line in and one response per line out.
+/// `{"reset":{"seed":1}}` answers with an observation, `{"step":{"key":"left"}}`, `{"step":"wait"}`
+/// and `{"step":{"placement":3}}` with a step, and `"close"` ends it.
+pub fn serve<R: BufRead, W: Write>(env: &mut Env, reader: R, mut writer: W) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Reset { seed }) => serde_json::to_string(&env.reset(seed.unwrap_or_else(rand::random))),
+            Ok(Request::Step(action)) => match env.step(action) {
+                Ok(step) => serde_json::to_string(&step),
+                Err(error) => serde_json::to_string(&Error { error }),
+            },
+            Ok(Request::Close) => return Ok(()),
+            Err(err) => serde_json::to_string(&Error { error: err.to_string() }),
+        }?;
+
+        writeln!(writer, "{}", response)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_zero_frame_still_waits_out_the_delays() {
+        let mut env = Env::new(Rules::default(), ActionSpace::Placements);
+        env.set_frame(Duration::ZERO);
+        env.reset(1);
+
+        let step = env.step(Action::Placement(0)).unwrap();
+        assert!(step.observation.current.is_some());
+        assert_eq!(step.info.pieces, 1);
+    }
+
+    // every response serve gives to the requests, one per line
+    fn serve_lines(action_space: ActionSpace, requests: &[&str]) -> Vec<serde_json::Value> {
+        let mut env = Env::new(Rules::default(), action_space);
+        let mut out = Vec::new();
+        serve(&mut env, requests.join("\n").as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn keys_are_played_a_frame_at_a_time() {
+        let responses = serve_lines(ActionSpace::Keys, &[
+            r#"{"reset":{"seed":3}}"#,
+            r#"{"step":{"key":"left"}}"#,
+            r#""wait""#,
+            r#"{"step":"wait"}"#,
+            r#"{"step":{"key":"hard_drop"}}"#,
+            r#"{"step":{"placement":0}}"#,
+            r#"{"reset":{"seed":3}}"#,
+            r#""close""#,
+            r#"{"reset":{"seed":4}}"#,
+        ]);
+        assert_eq!(responses.len(), 7);
+
+        let reset = &responses[0];
+        assert!(reset.get("placements").is_none());
+        assert_eq!(responses[1]["observation"]["current"]["x"], reset["current"]["x"].as_i64().unwrap() - 1);
+        assert!(responses[2]["error"].is_string());
+        assert_eq!(responses[3]["observation"]["current"]["x"], responses[1]["observation"]["current"]["x"]);
+
+        let dropped = &responses[4];
+        assert_eq!(dropped["info"]["pieces"], 1);
+        assert_eq!(dropped["reward"], 0.0);
+        assert!(dropped["observation"]["board"].as_array().unwrap().last().unwrap().as_array().unwrap().contains(&1.into()));
+
+        assert_eq!(responses[5]["error"], "expected a key or wait action");
+        assert_eq!(responses[6], responses[0]);
+    }
+
+    #[test]
+    fn placements_are_played_a_piece_at_a_time() {
+        let responses = serve_lines(ActionSpace::Placements, &[
+            r#"{"reset":{"seed":3}}"#,
+            r#"{"step":{"placement":0}}"#,
+            r#"{"step":{"placement":1000}}"#,
+            r#"{"step":{"key":"left"}}"#,
+            r#"{"reset":{"seed":3}}"#,
+        ]);
+
+        let reset = &responses[0];
+        let placements = reset["placements"].as_array().unwrap();
+        assert!(placements.iter().any(|placement| placement["hold"] == true));
+        assert!(reset["current"].is_object());
+
+        let step = &responses[1];
+        assert_eq!(step["info"]["pieces"], 1);
+        assert_eq!(step["done"], false);
+        assert_eq!(step["observation"]["current"]["type"], reset["queue"][0]);
+        let placements = step["observation"]["placements"].as_array().unwrap();
+        assert_eq!(responses[2]["error"], format!("there's no placement 1000, there are {}", placements.len()));
+        assert_eq!(responses[3]["error"], "expected a placement action");
+        assert_eq!(responses[4], responses[0]);
+    }
+}
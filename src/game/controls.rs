@@ -0,0 +1,145 @@
+use termion::event::Key;
+
+use crate::game::engine::Input;
+
+/// Which keys play which inputs, and how they're listed in the help window.
+pub struct Controls {
+    bindings: Vec<(Key, Input)>,
+    help: Vec<(&'static str, &'static str)>,
+}
+
+impl Controls {
+    /// The keys for playing alone.
+    pub fn single() -> Self {
+        Controls {
+            bindings: vec![
+                (Key::Char('j'), Input::Left),
+                (Key::Left, Input::Left),
+                (Key::Char('l'), Input::Right),
+                (Key::Right, Input::Right),
+                (Key::Char('k'), Input::SoftDrop),
+                (Key::Down, Input::SoftDrop),
+                (Key::Char(' '), Input::HardDrop),
+                (Key::Char('x'), Input::RotateClockwise),
+                (Key::Char('z'), Input::RotateCounterClockwise),
+                (Key::Char('c'), Input::Hold),
+            ],
+            help: vec![
+                ("left", "j, ←"),
+                ("right", "l, →"),
+                ("down", "k, ↓"),
+                ("drop", "space"),
+                ("rotate", "x, z"),
+                ("hold", "c"),
+            ],
+        }
+    }
+
+    /// The player on the left side of the keyboard when two are sharing it.
+    pub fn left_hand() -> Self {
+        Controls {
+            bindings: vec![
+                (Key::Char('a'), Input::Left),
+                (Key::Char('d'), Input::Right),
+                (Key::Char('s'), Input::SoftDrop),
+                (Key::Char('w'), Input::HardDrop),
+                (Key::Char('e'), Input::RotateClockwise),
+                (Key::Char('q'), Input::RotateCounterClockwise),
+                (Key::Char('c'), Input::Hold),
+            ],
+            help: vec![
+                ("left", "a"),
+                ("right", "d"),
+                ("down", "s"),
+                ("drop", "w"),
+                ("rotate", "e, q"),
+                ("hold", "c"),
+            ],
+        }
+    }
+
+    /// The player on the arrow keys when two are sharing the keyboard.
+    pub fn right_hand() -> Self {
+        Controls {
+            bindings: vec![
+                (Key::Left, Input::Left),
+                (Key::Right, Input::Right),
+                (Key::Down, Input::SoftDrop),
+                (Key::Up, Input::HardDrop),
+                (Key::Char('.'), Input::RotateClockwise),
+                (Key::Char(','), Input::RotateCounterClockwise),
+                (Key::Char('/'), Input::Hold),
+            ],
+            help: vec![
+                ("left", "←"),
+                ("right", "→"),
+                ("down", "↓"),
+                ("drop", "↑"),
+                ("rotate", "., ,"),
+                ("hold", "/"),
+            ],
+        }
+    }
+
+    /// Both hands' keys listed together, for the help window of a co-op board. The inputs are
+    /// read from `left_hand` and `right_hand` since each moves a different piece.
+    pub fn coop() -> Self {
+        Controls {
+            bindings: Vec::new(),
+            help: vec![
+                ("left", "a | ←"),
+                ("right", "d | →"),
+                ("down", "s | ↓"),
+                ("drop", "w | ↑"),
+                ("rotate", "e,q | .,,"),
+                ("hold", "c | /"),
+            ],
+        }
+    }
+
+    /// For a board nobody plays from this keyboard, like the other side of an online game.
+    pub fn none() -> Self {
+        Controls { bindings: Vec::new(), help: Vec::new() }
+    }
+
+    pub fn input(&self, key: Key) -> Option<Input> {
+        self.bindings.iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, input)| *input)
+    }
+
+    pub fn help(&self) -> &[(&'static str, &'static str)] {
+        &self.help
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUTS: [Input; 7] = [Input::Left, Input::Right, Input::SoftDrop, Input::HardDrop, Input::RotateClockwise, Input::RotateCounterClockwise, Input::Hold];
+
+    #[test]
+    fn every_layout_plays_every_input() {
+        for controls in [Controls::single(), Controls::left_hand(), Controls::right_hand()] {
+            for input in INPUTS {
+                assert!(controls.bindings.iter().any(|(_, bound)| *bound == input), "{:?}", input);
+            }
+            assert_eq!(controls.help().len(), 6);
+        }
+    }
+
+    // one key press only ever moves one player's piece, and doesn't end the round
+    #[test]
+    fn the_hands_share_no_keys() {
+        let (left, right) = (Controls::left_hand(), Controls::right_hand());
+        for (key, _) in &left.bindings {
+            assert_eq!(right.input(*key), None, "{:?}", key);
+        }
+
+        for controls in [left, right] {
+            assert_eq!(controls.input(Key::Esc), None);
+            assert_eq!(controls.input(Key::Char('\n')), None);
+        }
+    }
+}
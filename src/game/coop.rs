@@ -0,0 +1,85 @@
+use std::io::{Read, Result, Write};
+use std::thread;
+use std::time::Duration;
+
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Rules};
+use crate::game::view::View;
+
+/// Two players on one keyboard building one stack on a board twice as wide, each moving their
+/// own piece. The next queue, hold and score are shared.
+pub struct Coop<R: Read, W: Write> {
+    stdin: Keys<R>,
+    stdout: W,
+    rules: Rules,
+    engine: Engine,
+    view: View,
+
+    // the left hand moves the piece which comes in on the left, the right hand the other one
+    controls: [Controls; 2],
+}
+
+impl<R: Read, W: Write> Coop<R, W> {
+    pub fn new(r: R, w: W, rules: Rules) -> Coop<R, RawTerminal<W>> {
+        let mut view = View::new(1, 1, Controls::coop());
+        view.set_quit_key(Some("esc"));
+
+        Coop {
+            stdin: r.keys(),
+            stdout: w.into_raw_mode().unwrap(),
+            rules,
+            engine: Engine::with_coop(rules, rand::random()),
+            view,
+            controls: [Controls::left_hand(), Controls::right_hand()],
+        }
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        self.view.set_animations(animations);
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
+
+        'main: loop {
+            thread::sleep(Duration::from_millis(50));
+
+            // both players can press keys in the same frame, so everything waiting is read
+            while let Some(Ok(key)) = self.stdin.next() {
+                match key {
+                    Key::Esc => break 'main,
+                    Key::Char('\n') if self.engine.is_over() => self.new_game(),
+                    key => {
+                        for (player, controls) in self.controls.iter().enumerate() {
+                            if let Some(input) = controls.input(key) {
+                                self.engine.player_input(player, input);
+                            }
+                        }
+                    }
+                }
+            }
+
+            self.engine.tick(Duration::from_millis(50));
+            if self.engine.is_over() {
+                let score = format!("{} points", self.engine.stats().score);
+                self.view.set_banner(vec!["TOP OUT".to_string(), score, "enter: again".to_string()]);
+            }
+
+            self.view.draw(&mut self.stdout, &self.engine)?;
+            self.stdout.flush()?;
+        }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+
+        Ok(())
+    }
+
+    fn new_game(&mut self) {
+        self.engine = Engine::with_coop(self.rules, rand::random());
+        self.view.reset();
+    }
+}
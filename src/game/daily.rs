@@ -0,0 +1,295 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Engine, Mode, Rules};
+use crate::game::random::{Randomizer, Rng};
+use crate::storage;
+
+const LEADERBOARD_FILE: &str = "daily.json";
+
+// the daily dig is shorter than the default one
+const DIG_ROWS: usize = 10;
+const DIG_TARGET: usize = 20;
+
+/// A day in the proleptic Gregorian calendar, counted from 1970-01-01.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Date {
+    days: i64,
+}
+
+impl Date {
+    /// Today in UTC, so everyone gets the same challenge whatever their timezone.
+    pub fn today() -> Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+        Date { days: (seconds / 86400) as i64 }
+    }
+
+    /// Parses a date written like 2024-05-31.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, '-');
+        let year: i64 = parts.next()?.parse().ok()?;
+        let month: i64 = parts.next()?.parse().ok()?;
+        let day: i64 = parts.next()?.parse().ok()?;
+
+        let date = Date::from_civil(year, month, day);
+        (date.civil() == (year, month, day)).then_some(date)
+    }
+
+    // days from the civil date, after Howard Hinnant's days_from_civil
+    fn from_civil(year: i64, month: i64, day: i64) -> Self {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Date { days: era * 146097 + day_of_era - 719468 }
+    }
+
+    fn civil(self) -> (i64, i64, i64) {
+        let days = self.days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.civil();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// The game everyone plays on a given day, the seed, mode and rules all come from the date.
+pub struct Challenge {
+    pub date: Date,
+    pub seed: u64,
+    pub mode: Mode,
+    pub rules: Rules,
+}
+
+impl Challenge {
+    pub fn today() -> Self {
+        Self::for_date(Date::today())
+    }
+
+    pub fn for_date(date: Date) -> Self {
+        let mut rng = Rng::new(date.days as u64);
+        let seed = rng.next_u64();
+        let mode = [Mode::Sprint, Mode::Ultra, Mode::Dig][rng.below(3)];
+
+        // most days are played by the usual rules, some days aren't
+        let mut rules = Rules::default();
+        if rng.below(4) == 0 {
+            rules.randomizer = Randomizer::Random;
+        }
+        if rng.below(5) == 0 {
+            rules.hold = false;
+        }
+
+        Challenge { date, seed, mode, rules }
+    }
+
+    pub fn engine(&self) -> Engine {
+        match self.mode {
+            Mode::Dig => Engine::with_dig(self.rules, self.seed, DIG_ROWS, DIG_TARGET),
+            mode => Engine::with_seed(self.rules, mode, self.seed),
+        }
+    }
+
+    /// What the day's challenge is, like "sprint, 7-bag, hold".
+    pub fn description(&self) -> String {
+        let mode = match self.mode {
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+            _ => "dig",
+        };
+        let randomizer = match self.rules.randomizer {
+            Randomizer::SevenBag => "7-bag",
+            Randomizer::Random => "random pieces",
+        };
+        let hold = if self.rules.hold { "hold" } else { "no hold" };
+        format!("{}, {}, {}", mode, randomizer, hold)
+    }
+}
+
+/// One player's result of one day's challenge.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub date: String,
+    pub name: String,
+
+    /// The day's seed, results from a build which derives another challenge are left out.
+    pub seed: u64,
+    pub mode: Mode,
+
+    /// Milliseconds of play.
+    pub time: u64,
+    pub lines: usize,
+    pub score: usize,
+    pub pieces: usize,
+}
+
+impl Entry {
+    /// The result of a finished challenge, none if it was topped out or quit.
+    pub fn new(challenge: &Challenge, name: &str, engine: &Engine) -> Option<Self> {
+        if !engine.is_won() {
+            return None;
+        }
+
+        let stats = engine.stats();
+        Some(Entry {
+            date: challenge.date.to_string(),
+            name: name.to_string(),
+            seed: challenge.seed,
+            mode: challenge.mode,
+            time: engine.played().as_millis() as u64,
+            lines: stats.lines,
+            score: stats.score,
+            pieces: stats.pieces,
+        })
+    }
+
+    // ultra is won on points, the others on time
+    fn beats(&self, other: &Entry) -> bool {
+        match self.mode {
+            Mode::Ultra => self.score > other.score,
+            _ => self.time < other.time,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        Date::parse(&self.date).map(Challenge::for_date).is_some_and(|day| day.seed == self.seed && day.mode == self.mode)
+    }
+}
+
+/// The best result of every player on every day, kept in the data directory. Other players'
+/// leaderboard files can be merged in to compare.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    pub fn load() -> io::Result<Self> {
+        storage::load(LEADERBOARD_FILE)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save(LEADERBOARD_FILE, self)
+    }
+
+    /// Someone else's leaderboard file.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Keeps the entry if it's the player's first or best of the day, returns whether it was.
+    pub fn record(&mut self, entry: Entry) -> bool {
+        if !entry.is_valid() {
+            return false;
+        }
+
+        match self.entries.iter_mut().find(|kept| kept.date == entry.date && kept.name == entry.name) {
+            Some(kept) if entry.beats(kept) => *kept = entry,
+            Some(_) => return false,
+            None => self.entries.push(entry),
+        }
+        true
+    }
+
+    /// Takes in the other leaderboard's entries, returns how many were new or better.
+    pub fn merge(&mut self, other: Leaderboard) -> usize {
+        let mut added = 0;
+        for entry in other.entries {
+            if self.record(entry) {
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// The day's entries, best first.
+    pub fn standings(&self, date: Date) -> Vec<&Entry> {
+        let date = date.to_string();
+        let mut entries: Vec<&Entry> = self.entries.iter().filter(|entry| entry.date == date).collect();
+        entries.sort_by(|a, b| match a.mode {
+            Mode::Ultra => b.score.cmp(&a.score),
+            _ => a.time.cmp(&b.time),
+        }.then_with(|| a.name.cmp(&b.name)));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> Date {
+        Date::parse(value).unwrap()
+    }
+
+    #[test]
+    fn dates_are_counted_from_the_epoch() {
+        assert_eq!(date("1970-01-01").days, 0);
+        assert_eq!(date("1969-12-31").days, -1);
+        assert_eq!(date("2000-03-01").days, 11017);
+        assert_eq!(date("2024-02-29").days, 19782);
+        assert_eq!(date("2024-02-29").to_string(), "2024-02-29");
+    }
+
+    #[test]
+    fn days_which_dont_exist_are_turned_away() {
+        assert!(Date::parse("2023-02-29").is_none());
+        assert!(Date::parse("1900-02-29").is_none());
+        assert!(Date::parse("2024-13-01").is_none());
+        assert!(Date::parse("2024-04-31").is_none());
+        assert!(Date::parse("2024-05").is_none());
+        assert!(Date::parse("2000-02-29").is_some());
+    }
+
+    // everyone's leaderboards depend on these staying the same from build to build
+    #[test]
+    fn the_challenge_comes_from_the_date() {
+        let challenge = Challenge::for_date(date("2024-05-31"));
+        assert_eq!(challenge.seed, 11304177911056183356);
+        assert_eq!(challenge.description(), "dig, 7-bag, hold");
+
+        assert_eq!(Challenge::for_date(date("2024-05-31")).seed, challenge.seed);
+        assert_ne!(Challenge::for_date(date("2024-06-01")).seed, challenge.seed);
+    }
+
+    #[test]
+    fn entries_for_another_days_seed_are_left_out() {
+        let challenge = Challenge::for_date(date("2024-05-31"));
+        let entry = Entry {
+            date: challenge.date.to_string(),
+            name: "someone".to_string(),
+            seed: challenge.seed,
+            mode: challenge.mode,
+            time: 60000,
+            lines: 20,
+            score: 0,
+            pieces: 60,
+        };
+
+        let mut leaderboard = Leaderboard::default();
+        assert!(!leaderboard.record(Entry { seed: challenge.seed + 1, ..entry.clone() }));
+        assert!(leaderboard.record(entry.clone()));
+        assert!(!leaderboard.record(Entry { time: 70000, ..entry.clone() }));
+        assert!(leaderboard.record(Entry { time: 50000, ..entry }));
+        assert_eq!(leaderboard.standings(challenge.date).len(), 1);
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::Board;
+use crate::game::garbage::HoleGenerator;
+
+/// A cheese race, the board starts with garbage rows and more rise as they're cleared until
+/// the target has been dug out.
+#[derive(Serialize, Deserialize)]
+pub struct Dig {
+    /// Garbage rows kept on the board while there's more to dig.
+    pub rows: usize,
+
+    /// Garbage lines to dig out in total.
+    pub target: usize,
+    pub dug: usize,
+
+    // garbage rows on the board or still to come, never more than the target
+    added: usize,
+    holes: HoleGenerator,
+}
+
+impl Dig {
+    pub fn new(rows: usize, target: usize, holes: HoleGenerator) -> Self {
+        Dig { rows, target, dug: 0, added: 0, holes }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.dug >= self.target
+    }
+
+    /// Brings the garbage on the board back up to `rows`, returns whether the stack was pushed
+    /// off the top.
+    pub(crate) fn fill(&mut self, board: &mut Board) -> bool {
+        let on_board = self.added - self.dug;
+        let lines = self.rows.saturating_sub(on_board).min(self.target - self.added);
+        self.added += lines;
+        lines > 0 && board.insert_garbage(&self.holes.holes(lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::garbage::Holes;
+    use crate::game::random::Rng;
+
+    fn garbage_rows(board: &Board) -> usize {
+        board.rows().iter().filter(|row| row.contains('G')).count()
+    }
+
+    #[test]
+    fn garbage_rises_until_the_target_is_on_the_board() {
+        let mut board = Board::new();
+        let mut dig = Dig::new(5, 8, HoleGenerator::new(Holes::Cheese, Rng::new(1)));
+        assert!(!dig.fill(&mut board));
+        assert_eq!(garbage_rows(&board), 5);
+
+        // only rows which were dug out are replaced
+        assert!(!dig.fill(&mut board));
+        assert_eq!(garbage_rows(&board), 5);
+
+        dig.dug = 3;
+        dig.fill(&mut board);
+        assert_eq!(garbage_rows(&board), 8);
+
+        // there's nothing left to add
+        dig.dug = 6;
+        dig.fill(&mut board);
+        assert_eq!(garbage_rows(&board), 8);
+        assert!(!dig.is_done());
+
+        dig.dug = 8;
+        assert!(dig.is_done());
+    }
+
+    #[test]
+    fn every_garbage_row_has_one_hole() {
+        let mut board = Board::new();
+        Dig::new(10, 10, HoleGenerator::new(Holes::Cheese, Rng::new(2))).fill(&mut board);
+        let rows = board.rows();
+        for row in rows.iter().rev().take(10) {
+            assert_eq!(row.matches('.').count(), 1, "{}", row);
+        }
+    }
+}
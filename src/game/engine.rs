@@ -0,0 +1,1586 @@
+use std::time::Duration;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use termion::color::Rgb;
+use crate::game::dig::Dig;
+use crate::game::finesse::{Drill, Finesse};
+use crate::game::fumen::Page;
+use crate::game::garbage::{self, HoleGenerator, Holes};
+use crate::game::puzzle::{Goal, Puzzle};
+use crate::game::random::{Generator, Randomizer, Rng};
+use crate::game::snapshot::{Piece, Snapshot};
+use crate::game::tetrimino::{Tetrimino, Type};
+
+pub const BOARD_WIDTH: u16 = 10;
+pub const BOARD_HEIGHT: u16 = 20;
+
+/// The shared board of a co-op game is twice as wide, it's also the widest a board can be.
+pub const COOP_BOARD_WIDTH: u16 = BOARD_WIDTH * 2;
+
+pub const GARBAGE_COLOR: Rgb = Rgb(128, 128, 128);
+
+// how long a callout such as "B2B TETRIS" stays beside the board
+const CALLOUT_DURATION: Duration = Duration::from_millis(2000);
+const PERFECT_CLEAR_DURATION: Duration = Duration::from_millis(3000);
+
+/// Lines to clear in a sprint, and how long an ultra lasts.
+pub const SPRINT_LINES: usize = 40;
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+
+// moving or rotating a piece on the ground restarts its lock delay, but only this many times
+const MAX_LOCK_RESETS: usize = 15;
+
+/// Timings and rules the engine plays by, all of the delays can be zero to skip them entirely.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Rules {
+    pub fall_rate: Duration,
+
+    /// How long a piece can sit on the ground before it locks, zero locks it as soon as it lands.
+    pub lock_delay: Duration,
+
+    /// How long cleared rows stay on the board before they collapse.
+    pub line_clear_delay: Duration,
+
+    /// How long newly locked cells flash for, this doesn't hold up the game.
+    pub lock_flash: Duration,
+
+    /// Entry delay (ARE), the pause between a piece locking and the next one spawning.
+    pub entry_delay: Duration,
+
+    pub randomizer: Randomizer,
+    pub hold: bool,
+
+    /// Where the holes go in garbage rows.
+    pub garbage: Holes,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            fall_rate: Duration::from_millis(500),
+            lock_delay: Duration::from_millis(500),
+            line_clear_delay: Duration::from_millis(300),
+            lock_flash: Duration::from_millis(100),
+            entry_delay: Duration::from_millis(100),
+            randomizer: Randomizer::SevenBag,
+            hold: true,
+            garbage: Holes::Clean,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Endless play until the stack tops out.
+    Marathon,
+
+    /// Finesse practice, each piece has a target spot on an empty board.
+    Drill,
+
+    /// A set board and queue with a goal to reach before the pieces run out.
+    Puzzle,
+
+    /// Digging through rows of garbage as fast as possible.
+    Dig,
+
+    /// Clearing `SPRINT_LINES` lines as fast as possible.
+    Sprint,
+
+    /// As many points as possible before `ULTRA_TIME` runs out.
+    Ultra,
+
+    /// Two players on one wide board, each with their own piece, until the stack tops out.
+    Coop,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Input {
+    Left,
+    Right,
+    SoftDrop,
+
+    /// Drops the piece all the way down without locking it, like holding soft drop.
+    SonicDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Hold,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Stats {
+    pub score: usize,
+    pub lines: usize,
+    pub perfect_clears: usize,
+    pub pieces: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Engine {
+    rules: Rules,
+    mode: Mode,
+    stats: Stats,
+    board: Board,
+    state: State,
+    phase: Phase,
+
+    // one for each player, only co-op games have more than one
+    seats: Vec<Seat>,
+
+    // the queue and hold are shared by everyone on the board
+    next_type: Option<Type>,
+    held_type: Option<Type>,
+    combo: isize,
+    back_to_back: bool,
+    callouts: Vec<String>,
+    callout_elapsed: Duration,
+    perfect_clear_elapsed: Option<Duration>,
+    lock_flash: Vec<(usize, usize)>,
+    lock_flash_elapsed: Duration,
+
+    // time spent playing, the result of races
+    played: Duration,
+    seed: u64,
+    generator: Generator,
+    finesse: Option<Finesse>,
+    drill: Option<Drill>,
+    goal: Option<Goal>,
+    dig: Option<Dig>,
+
+    // the comment of the fumen the game started from
+    comment: Option<String>,
+
+    // garbage lines sent by clears which haven't been taken by the other player yet,
+    // and lines received which go in under the stack once a piece locks without clearing
+    attack: usize,
+    incoming: garbage::Queue,
+    garbage: HoleGenerator,
+}
+
+#[derive(Serialize, Deserialize)]
+enum State {
+    Playing,
+    Lost,
+
+    // the goal of a puzzle was reached
+    Won,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Phase {
+    Falling,
+
+    // the cleared rows are still on the board until the delay is over, every piece waits
+    Clearing { rows: Vec<usize>, elapsed: Duration },
+}
+
+// a player's falling piece and everything which starts over with each new one
+#[derive(Serialize, Deserialize)]
+struct Seat {
+    active: ActiveTetrimino,
+
+    // the piece has locked and the next one comes in once the entry delay is over
+    entry: Option<Duration>,
+
+    // how far right of the usual spot this player's pieces come in
+    spawn_x: isize,
+    can_hold: bool,
+    elapsed: Duration,
+    lock_elapsed: Duration,
+    lock_resets: usize,
+
+    // the lowest row the piece has been down to, getting lower gives the lock resets back
+    lowest: isize,
+}
+
+impl Seat {
+    fn new(active: ActiveTetrimino, spawn_x: isize) -> Self {
+        Seat {
+            lowest: active.y,
+            active,
+            entry: None,
+            spawn_x,
+            can_hold: true,
+            elapsed: Duration::from_millis(0),
+            lock_elapsed: Duration::from_millis(0),
+            lock_resets: 0,
+        }
+    }
+
+    // starts the lock delay and its resets over when the piece is lower than it has been yet
+    fn descend(&mut self) -> bool {
+        if self.active.y <= self.lowest {
+            return false;
+        }
+
+        self.lowest = self.active.y;
+        self.lock_elapsed = Duration::from_millis(0);
+        self.lock_resets = 0;
+        true
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ActiveTetrimino {
+    pub tetrimino: Tetrimino,
+
+    // These must be isize because tetrimino's grid can go out of bound
+    // e.g block L has this shape on the left.
+    //
+    // 0 1 0 0      If this block is at the far left corner
+    // 0 1 0 0      the x will be -1, doesn't have a good solution for this yet
+    // 0 1 1 1      And this is why there are a lot of type casts in the code
+    // 0 0 0 0      Not great
+    pub x: isize,
+    pub y: isize,
+
+    // the kick used if the last successful action was a rotation, needed for t-spin detection
+    last_kick: Option<usize>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What a locked piece did, whether or not it cleared any lines.
+#[derive(Copy, Clone)]
+pub struct Clear {
+    pub lines: usize,
+    pub spin: Spin,
+    pub back_to_back: bool,
+
+    /// Pieces in a row which have cleared lines before this one, -1 when it didn't clear any.
+    pub combo: isize,
+    pub perfect_clear: bool,
+}
+
+impl Engine {
+    pub fn new(rules: Rules) -> Self {
+        Self::with_mode(rules, Mode::Marathon)
+    }
+
+    pub fn with_mode(rules: Rules, mode: Mode) -> Self {
+        Self::with_seed(rules, mode, rand::random())
+    }
+
+    /// The same seed, rules and inputs always play out the same game.
+    pub fn with_seed(rules: Rules, mode: Mode, seed: u64) -> Self {
+        let mut generator = Generator::new(rules.randomizer, seed);
+        let current_ttype = generator.next().unwrap();
+        let next_ttype = generator.next().unwrap();
+
+        let mut engine = Engine {
+            rules,
+            mode,
+            stats: Stats::default(),
+            board: Board::new(),
+            state: State::Playing,
+            phase: Phase::Falling,
+            seats: vec![Seat::new(Self::initialize_tetrimino(current_ttype), 0)],
+            next_type: Some(next_ttype),
+            held_type: None,
+            combo: -1,
+            back_to_back: false,
+            callouts: Vec::new(),
+            callout_elapsed: Duration::from_millis(0),
+            perfect_clear_elapsed: None,
+            lock_flash: Vec::new(),
+            lock_flash_elapsed: Duration::from_millis(0),
+            played: Duration::from_millis(0),
+            seed,
+            generator,
+            finesse: None,
+            drill: None,
+            goal: None,
+            comment: None,
+            dig: None,
+            attack: 0,
+            incoming: garbage::Queue::default(),
+            garbage: HoleGenerator::new(rules.garbage, Rng::new(seed.rotate_left(32))),
+        };
+
+        if let Mode::Drill = mode {
+            // targets come from their own generator so they don't change the pieces dealt
+            let mut drill = Drill::new(Rng::new(!seed));
+            drill.pick_target(current_ttype, rules);
+            engine.drill = Some(drill);
+            engine.enable_finesse();
+        }
+
+        engine
+    }
+
+    /// Starts with `rows` rows of garbage on the board, the game is won once `target` garbage
+    /// lines have been cleared.
+    pub fn with_dig(rules: Rules, seed: u64, rows: usize, target: usize) -> Self {
+        let mut engine = Self::with_seed(rules, Mode::Dig, seed);
+
+        // the holes always move from row to row, and come from the garbage stream which is
+        // otherwise unused since nobody sends garbage in this mode
+        let holes = HoleGenerator::new(Holes::Cheese, Rng::new(seed.rotate_left(32)));
+        let mut dig = Dig::new(rows.min(BOARD_HEIGHT as usize - 4), target, holes);
+        dig.fill(&mut engine.board);
+        engine.dig = Some(dig);
+        engine
+    }
+
+    /// Two players on a board twice as wide, the left player's pieces come in over the left
+    /// half and the right player's over the right half.
+    pub fn with_coop(rules: Rules, seed: u64) -> Self {
+        let mut engine = Self::with_seed(rules, Mode::Coop, seed);
+        engine.board = Board::with_width(COOP_BOARD_WIDTH as usize);
+
+        let ttype = engine.next_type.take().unwrap();
+        engine.next_type = engine.generator.next();
+        let spawn_x = BOARD_WIDTH as isize;
+        let mut active = Self::initialize_tetrimino(ttype);
+        active.x += spawn_x;
+        engine.seats.push(Seat::new(active, spawn_x));
+        engine
+    }
+
+    /// Sets up the puzzle's board, queue and hold, the game is won once its goal is reached.
+    pub fn with_puzzle(rules: Rules, puzzle: &Puzzle) -> Result<Self, String> {
+        let mut queue = puzzle.queue()?;
+        if queue.is_empty() {
+            return Err(format!("puzzle {} has no pieces", puzzle.name));
+        }
+
+        let current_ttype = queue.remove(0);
+        let next_ttype = if queue.is_empty() { None } else { Some(queue.remove(0)) };
+
+        let mut engine = Self::with_seed(rules, Mode::Puzzle, 0);
+        engine.board = puzzle.board()?;
+        engine.generator = Generator::fixed(queue);
+        engine.seats[0].active = Self::initialize_tetrimino(current_ttype);
+        engine.seats[0].lowest = engine.seats[0].active.y;
+        engine.next_type = next_ttype;
+        engine.held_type = puzzle.hold()?;
+        engine.goal = Some(puzzle.goal);
+        Ok(engine)
+    }
+
+    /// Starts from the fumen page's board, with its piece falling if it has one.
+    pub fn with_fumen(rules: Rules, seed: u64, page: &Page) -> Result<Self, String> {
+        let mut engine = Self::with_seed(rules, Mode::Marathon, seed);
+        engine.board = page.board()?;
+        if let Some(piece) = page.piece {
+            let tetrimino = Tetrimino { ttype: piece.ttype, state: piece.state % 4 };
+            if !engine.board.can_fit(piece.x, piece.y, tetrimino.block()) {
+                return Err("the fumen's piece doesn't fit on its board".to_string());
+            }
+            engine.seats[0].active = ActiveTetrimino { tetrimino, x: piece.x, y: piece.y, last_kick: None };
+            engine.seats[0].lowest = piece.y;
+        }
+        engine.comment = Some(page.comment.clone()).filter(|comment| !comment.is_empty());
+        Ok(engine)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.rows(),
+            active: self.active().map(|active| Piece {
+                ttype: active.tetrimino.ttype,
+                x: active.x,
+                y: active.y,
+                state: active.tetrimino.state,
+            }),
+            next: self.next_type,
+            hold: self.held_type,
+            score: self.stats.score,
+            lines: self.stats.lines,
+            pieces: self.stats.pieces,
+            perfect_clears: self.stats.perfect_clears,
+            callouts: self.callouts.clone(),
+            perfect_clear: self.showing_perfect_clear(),
+            incoming: self.incoming.lines(),
+            lost: self.is_lost(),
+            won: self.is_won(),
+        }
+    }
+
+    /// Makes this engine look like the snapshot, for drawing a game which is played somewhere else.
+    /// The engine isn't meant to be played or ticked after this.
+    pub fn mirror(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        self.board = Board::from_rows(&snapshot.board)?;
+        self.phase = Phase::Falling;
+        self.seats[0].entry = Some(Duration::from_millis(0));
+        if let Some(piece) = snapshot.active {
+            self.seats[0].active = ActiveTetrimino {
+                tetrimino: Tetrimino { ttype: piece.ttype, state: piece.state % 4 },
+                x: piece.x,
+                y: piece.y,
+                last_kick: None,
+            };
+            self.seats[0].entry = None;
+        }
+
+        self.next_type = snapshot.next;
+        self.held_type = snapshot.hold;
+        self.stats = Stats {
+            score: snapshot.score,
+            lines: snapshot.lines,
+            pieces: snapshot.pieces,
+            perfect_clears: snapshot.perfect_clears,
+        };
+        self.callouts = snapshot.callouts.clone();
+        self.perfect_clear_elapsed = if snapshot.perfect_clear { Some(Duration::from_millis(0)) } else { None };
+        self.incoming.clear();
+        self.incoming.push(snapshot.incoming);
+        self.lock_flash.clear();
+        self.state = match (snapshot.lost, snapshot.won) {
+            (true, _) => State::Lost,
+            (_, true) => State::Won,
+            _ => State::Playing,
+        };
+
+        Ok(())
+    }
+
+    /// Starts counting finesse faults, drills always count them.
+    pub fn enable_finesse(&mut self) {
+        if self.finesse.is_none() {
+            self.finesse = Some(Finesse::new());
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn finesse(&self) -> Option<&Finesse> {
+        self.finesse.as_ref()
+    }
+
+    pub fn drill(&self) -> Option<&Drill> {
+        self.drill.as_ref()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// The falling piece, there's none while lines are clearing or the next piece is waiting to spawn.
+    pub fn active(&self) -> Option<&ActiveTetrimino> {
+        self.player_active(0)
+    }
+
+    /// How many players have a piece on the board, two in co-op and one otherwise.
+    pub fn players(&self) -> usize {
+        self.seats.len()
+    }
+
+    /// The player's falling piece, in co-op it stays put while the other player's lines clear.
+    pub fn player_active(&self, player: usize) -> Option<&ActiveTetrimino> {
+        match (&self.state, self.seats.get(player)) {
+            (State::Playing, Some(seat)) if seat.entry.is_none() => Some(&seat.active),
+            _ => None,
+        }
+    }
+
+    /// None once a puzzle's queue has run out.
+    pub fn next_type(&self) -> Option<Type> {
+        self.next_type
+    }
+
+    pub fn held_type(&self) -> Option<Type> {
+        self.held_type
+    }
+
+    pub fn can_hold(&self) -> bool {
+        self.player_can_hold(0)
+    }
+
+    fn player_can_hold(&self, player: usize) -> bool {
+        self.rules.hold && self.seats[player].can_hold && (self.held_type.is_some() || self.next_type.is_some())
+    }
+
+    /// Garbage the last clears sent, which is then gone from the engine.
+    pub fn take_attack(&mut self) -> usize {
+        std::mem::take(&mut self.attack)
+    }
+
+    pub fn receive_garbage(&mut self, lines: usize) {
+        self.incoming.push(lines);
+    }
+
+    /// Garbage which is going to rise once the next piece locks without clearing.
+    pub fn incoming_garbage(&self) -> usize {
+        self.incoming.lines()
+    }
+
+    pub fn goal(&self) -> Option<Goal> {
+        self.goal
+    }
+
+    pub fn dig(&self) -> Option<&Dig> {
+        self.dig.as_ref()
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// How long the game has been played for, not counting time after it ended.
+    pub fn played(&self) -> Duration {
+        self.played
+    }
+
+    /// How many pieces in a row have cleared lines, -1 when the last piece didn't clear any.
+    pub fn combo(&self) -> isize {
+        self.combo
+    }
+
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    pub fn is_lost(&self) -> bool {
+        matches!(self.state, State::Lost)
+    }
+
+    pub fn is_won(&self) -> bool {
+        matches!(self.state, State::Won)
+    }
+
+    /// Whether the game has ended, lost or won.
+    pub fn is_over(&self) -> bool {
+        !matches!(self.state, State::Playing)
+    }
+
+    pub fn callouts(&self) -> &[String] {
+        &self.callouts
+    }
+
+    pub fn showing_perfect_clear(&self) -> bool {
+        self.perfect_clear_elapsed.is_some()
+    }
+
+    /// Rows being cleared along with how far the clear has progressed, from 0.0 to 1.0.
+    pub fn clearing_rows(&self) -> Option<(&[usize], f32)> {
+        match &self.phase {
+            Phase::Clearing { rows, elapsed } => {
+                let progress = elapsed.as_secs_f32() / self.rules.line_clear_delay.as_secs_f32();
+                Some((rows, progress.min(1.0)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Cells of the piece which was locked last, while it's still flashing.
+    pub fn lock_flash(&self) -> &[(usize, usize)] {
+        &self.lock_flash
+    }
+
+    pub fn input(&mut self, input: Input) {
+        self.player_input(0, input);
+    }
+
+    /// An input for one of the players' pieces, co-op games have two.
+    pub fn player_input(&mut self, player: usize, input: Input) {
+        if self.player_active(player).is_none() || !matches!(self.phase, Phase::Falling) {
+            return;
+        }
+
+        if let Some(finesse) = &mut self.finesse {
+            finesse.record(input);
+        }
+
+        match input {
+            Input::Left => self.handle_tetrimino_move(player, -1, 0),
+            Input::Right => self.handle_tetrimino_move(player, 1, 0),
+            Input::SoftDrop => self.handle_tetrimino_move(player, 0, 1),
+            Input::SonicDrop => self.drop_current_tetrimino(player),
+            Input::HardDrop => {
+                self.drop_current_tetrimino(player);
+                self.fuse_current_tetrimino(player);
+            }
+            Input::RotateClockwise => self.handle_tetrimino_rotate(player, true),
+            Input::RotateCounterClockwise => self.handle_tetrimino_rotate(player, false),
+            Input::Hold => self.try_hold_tetrimino(player),
+        }
+
+        self.settle();
+    }
+
+    pub fn tick(&mut self, elapsed: Duration) {
+        if self.is_over() {
+            return;
+        }
+
+        self.played += elapsed;
+        if self.mode == Mode::Ultra && self.played >= ULTRA_TIME {
+            self.played = ULTRA_TIME;
+            self.state = State::Won;
+            return;
+        }
+        self.update_timers(elapsed);
+
+        match &mut self.phase {
+            Phase::Falling => {
+                for seat in 0..self.seats.len() {
+                    self.fall(seat, elapsed);
+                }
+            }
+            Phase::Clearing { elapsed: clearing, .. } => *clearing += elapsed,
+        }
+
+        self.settle();
+    }
+
+    // gravity, or the entry delay when the seat is waiting for its next piece
+    fn fall(&mut self, seat: usize, elapsed: Duration) {
+        if let Some(waiting) = &mut self.seats[seat].entry {
+            *waiting += elapsed;
+            return;
+        }
+
+        let active = &self.seats[seat].active;
+        let landed = !self.can_fit_tetrimino(seat, active.x, active.y + 1, active.tetrimino.block());
+        let seat = &mut self.seats[seat];
+
+        if landed {
+            seat.lock_elapsed += elapsed;
+        } else {
+            seat.elapsed += elapsed;
+
+            if seat.elapsed >= self.rules.fall_rate {
+                seat.elapsed -= self.rules.fall_rate;
+                seat.active.y += 1;
+                seat.active.last_kick = None;
+                seat.descend();
+            }
+        }
+    }
+
+    fn update_timers(&mut self, elapsed: Duration) {
+        if !self.callouts.is_empty() {
+            self.callout_elapsed += elapsed;
+            if self.callout_elapsed >= CALLOUT_DURATION {
+                self.callouts.clear();
+            }
+        }
+
+        if let Some(shown) = self.perfect_clear_elapsed {
+            let shown = shown + elapsed;
+            self.perfect_clear_elapsed = if shown >= PERFECT_CLEAR_DURATION { None } else { Some(shown) };
+        }
+
+        if !self.lock_flash.is_empty() {
+            self.lock_flash_elapsed += elapsed;
+            if self.lock_flash_elapsed >= self.rules.lock_flash {
+                self.lock_flash.clear();
+            }
+        }
+    }
+
+    // moves through every phase whose delay is already over, so zero delays don't cost a tick
+    fn settle(&mut self) {
+        while !self.is_over() {
+            match &self.phase {
+                Phase::Falling => {
+                    if !self.settle_seat() {
+                        return;
+                    }
+                }
+                Phase::Clearing { rows, elapsed } => {
+                    if *elapsed < self.rules.line_clear_delay {
+                        return;
+                    }
+                    let rows = rows.clone();
+                    self.board.collapse(&rows);
+                    self.lift_pieces();
+                    if self.mode == Mode::Sprint && self.stats.lines >= SPRINT_LINES {
+                        self.state = State::Won;
+                    }
+
+                    if let Some(dig) = &mut self.dig {
+                        if dig.fill(&mut self.board) {
+                            self.state = State::Lost;
+                        }
+                    }
+                    self.phase = Phase::Falling;
+                }
+            }
+        }
+    }
+
+    // locks the first piece which has been on the ground long enough, or brings in the next
+    // piece of the first seat done waiting, returns whether anything happened
+    fn settle_seat(&mut self) -> bool {
+        for seat in 0..self.seats.len() {
+            match self.seats[seat].entry {
+                None if self.should_fuse_current_tetrimino(seat) => {
+                    self.fuse_current_tetrimino(seat);
+                    return true;
+                }
+                Some(waiting) if waiting >= self.rules.entry_delay => {
+                    self.check_game_over();
+                    if self.spawn_next_tetrimino(seat) {
+                        return true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    // the rows under the other pieces fall away when lines clear, which can leave them inside
+    // the stack that comes down
+    fn lift_pieces(&mut self) {
+        for seat in 0..self.seats.len() {
+            while self.seats[seat].entry.is_none() {
+                let active = &self.seats[seat].active;
+                if active.y < 0 || self.can_fit_tetrimino(seat, active.x, active.y, active.tetrimino.block()) {
+                    break;
+                }
+                self.seats[seat].active.y -= 1;
+                self.seats[seat].lowest -= 1;
+            }
+        }
+    }
+
+    fn check_game_over(&mut self) {
+        for col in &self.board.blocks[0] {
+            if let Block::Occupied(_) = col {
+                self.state = State::Lost;
+                break;
+            }
+        }
+    }
+
+    // the next piece waits while the other player's piece is in the way, returns whether it came in
+    fn spawn_next_tetrimino(&mut self, seat: usize) -> bool {
+        if self.is_over() {
+            return true;
+        }
+
+        // when a puzzle's queue runs out the held piece is the last one to play
+        let ttype = match self.next_type.or(self.held_type) {
+            Some(ttype) => ttype,
+            None => {
+                self.state = State::Lost;
+                return true;
+            }
+        };
+
+        let active = self.spawn_tetrimino(seat, ttype);
+        if self.overlaps_others(seat, active.x, active.y, active.tetrimino.block()) {
+            return false;
+        }
+        if self.next_type.take().is_none() {
+            self.held_type = None;
+        }
+
+        self.seats[seat].active = active;
+        self.seats[seat].entry = None;
+        self.next_type = self.generator.next();
+        self.seats[seat].can_hold = true;
+        self.reset_for_new_piece(seat);
+        true
+    }
+
+    fn spawn_tetrimino(&self, seat: usize, ttype: Type) -> ActiveTetrimino {
+        let mut active = Self::initialize_tetrimino(ttype);
+        active.x += self.seats[seat].spawn_x;
+        active
+    }
+
+    // everything which starts over when a new piece comes in, from the queue or from hold
+    fn reset_for_new_piece(&mut self, seat: usize) {
+        let ttype = self.seats[seat].active.tetrimino.ttype;
+        let seat = &mut self.seats[seat];
+        seat.elapsed = Duration::from_millis(0);
+        seat.lock_elapsed = Duration::from_millis(0);
+        seat.lock_resets = 0;
+        seat.lowest = seat.active.y;
+
+        if let Some(finesse) = &mut self.finesse {
+            finesse.reset();
+        }
+        if let Some(drill) = &mut self.drill {
+            drill.pick_target(ttype, self.rules);
+        }
+    }
+
+    // move reset: a shift or rotation made on the ground puts the lock off again, up to
+    // MAX_LOCK_RESETS times, moves in the air and steps down don't use any up
+    fn reset_lock_delay(&mut self, seat: usize, grounded: bool) {
+        let seat = &mut self.seats[seat];
+        if !seat.descend() && grounded && seat.lock_resets < MAX_LOCK_RESETS {
+            seat.lock_elapsed = Duration::from_millis(0);
+            seat.lock_resets += 1;
+        }
+    }
+
+    fn drop_current_tetrimino(&mut self, seat: usize) {
+        for _ in 0..BOARD_HEIGHT {
+            self.handle_tetrimino_move(seat, 0, 1);
+        }
+    }
+
+    fn try_hold_tetrimino(&mut self, seat: usize) {
+        if !self.player_can_hold(seat) {
+            return;
+        }
+
+        let ttype = match self.held_type.or(self.next_type) {
+            Some(ttype) => ttype,
+            None => return,
+        };
+        let active = self.spawn_tetrimino(seat, ttype);
+        if self.overlaps_others(seat, active.x, active.y, active.tetrimino.block()) {
+            return;
+        }
+
+        if self.held_type.is_none() {
+            self.next_type = self.generator.next();
+        }
+        self.held_type = Some(self.seats[seat].active.tetrimino.ttype);
+        self.seats[seat].active = active;
+        self.seats[seat].can_hold = false;
+        self.reset_for_new_piece(seat);
+    }
+
+    fn handle_tetrimino_move(&mut self, seat: usize, dx: isize, dy: isize) {
+        let grounded = self.is_grounded(seat);
+        let active = &self.seats[seat].active;
+        let new_x = active.x + dx;
+        let new_y = active.y + dy;
+
+        if self.can_fit_tetrimino(seat, new_x, new_y, active.tetrimino.block()) {
+            let active = &mut self.seats[seat].active;
+            active.x = new_x;
+            active.y = new_y;
+            active.last_kick = None;
+            if dy == 0 {
+                self.reset_lock_delay(seat, grounded);
+            } else {
+                self.seats[seat].descend();
+            }
+        }
+    }
+
+    fn handle_tetrimino_rotate(&mut self, seat: usize, clockwise: bool) {
+        let grounded = self.is_grounded(seat);
+        let active = &self.seats[seat].active;
+        let fits = |x, y, block: &[[u8; 4]; 4]| self.can_fit_tetrimino(seat, x, y, block);
+
+        if let Some((tetrimino, x, y, kick)) = rotate_with(&active.tetrimino, active.x, active.y, clockwise, fits) {
+            let active = &mut self.seats[seat].active;
+            active.tetrimino = tetrimino;
+            active.x = x;
+            active.y = y;
+            active.last_kick = Some(kick);
+            self.reset_lock_delay(seat, grounded);
+        }
+    }
+
+    // the board and the other players' pieces are both in the way
+    fn can_fit_tetrimino(&self, seat: usize, block_x: isize, block_y: isize, block: &[[u8; 4]; 4]) -> bool {
+        self.board.can_fit(block_x, block_y, block) && !self.overlaps_others(seat, block_x, block_y, block)
+    }
+
+    fn overlaps_others(&self, seat: usize, block_x: isize, block_y: isize, block: &[[u8; 4]; 4]) -> bool {
+        let mut others = (0..self.seats.len())
+            .filter(|other| *other != seat && self.seats[*other].entry.is_none())
+            .map(|other| &self.seats[other].active)
+            .peekable();
+        if others.peek().is_none() {
+            return false;
+        }
+
+        let cells = block_cells(block_x, block_y, block);
+        others.any(|other| block_cells(other.x, other.y, other.tetrimino.block()).iter().any(|cell| cells.contains(cell)))
+    }
+
+    fn fuse_current_tetrimino(&mut self, seat: usize) {
+        let active = &self.seats[seat].active;
+        if let Some(finesse) = &mut self.finesse {
+            finesse.check(&self.board, active.tetrimino, active.x, active.y, self.rules);
+        }
+
+        self.lock_flash = self.board.place(&active.tetrimino, active.x, active.y);
+        self.lock_flash_elapsed = Duration::from_millis(0);
+        self.stats.pieces += 1;
+
+        // drills always start from an empty board, there's nothing to clear or score
+        if let Some(drill) = &mut self.drill {
+            drill.check(active.tetrimino, active.x, active.y);
+            self.board = Board::new();
+            self.seats[seat].entry = Some(Duration::from_millis(0));
+            return;
+        }
+
+        let spin = self.detect_spin(seat);
+        let rows = self.board.full_rows();
+        self.stats.lines += rows.len();
+        let clear = self.score_clear(&rows, spin);
+        // clears cancel incoming garbage before anything is sent
+        self.attack += self.incoming.cancel(garbage::attack(&clear));
+
+        if rows.is_empty() && !self.incoming.is_empty() {
+            self.rise_garbage();
+        }
+
+        if let Some(goal) = self.goal {
+            if goal.is_met(&clear, self.stats.lines) {
+                self.state = State::Won;
+            }
+        }
+
+        if let Some(dig) = &mut self.dig {
+            let board = &self.board;
+            dig.dug += rows.iter().filter(|y| board.is_garbage_row(**y)).count();
+            if dig.is_done() {
+                self.state = State::Won;
+            }
+        }
+
+        // the entry delay starts once the lines have cleared
+        self.seats[seat].entry = Some(Duration::from_millis(0));
+        if !rows.is_empty() {
+            self.phase = Phase::Clearing { rows, elapsed: Duration::from_millis(0) };
+        }
+    }
+
+    // all of the incoming garbage comes in at once, oldest attack at the bottom
+    fn rise_garbage(&mut self) {
+        let mut lines = 0;
+        for attack in self.incoming.take() {
+            let holes = self.garbage.holes(attack);
+            if self.board.insert_garbage(&holes) {
+                self.state = State::Lost;
+            }
+            lines += attack;
+        }
+
+        self.lock_flash = self.lock_flash.iter()
+            .filter(|(_, y)| *y >= lines)
+            .map(|(x, y)| (*x, y - lines))
+            .collect();
+    }
+
+    // t-spin detection with the 3-corner rule, the corners around the T's center are checked
+    // and it's a full spin if both corners on the side the T is pointing at are filled
+    // or the last kick was the far one which makes t-spin triples possible
+    fn detect_spin(&self, seat: usize) -> Spin {
+        let active = &self.seats[seat].active;
+        let kick = match active.last_kick {
+            Some(kick) if matches!(active.tetrimino.ttype, Type::T) => kick,
+            _ => return Spin::None,
+        };
+
+        let corners = [(0, 0), (2, 0), (2, 2), (0, 2)];
+        let filled: Vec<bool> = corners.iter()
+            .map(|(x, y)| self.is_blocked(active.x + x, active.y + y))
+            .collect();
+
+        if filled.iter().filter(|f| **f).count() < 3 {
+            return Spin::None;
+        }
+
+        // the two corners the T is pointing at, indexes into the corners above
+        let (a, b) = match active.tetrimino.state {
+            0 => (0, 1),
+            1 => (1, 2),
+            2 => (2, 3),
+            _ => (3, 0),
+        };
+
+        if (filled[a] && filled[b]) || kick == 4 {
+            Spin::Full
+        } else {
+            Spin::Mini
+        }
+    }
+
+    // out of bounds counts as blocked
+    fn is_blocked(&self, x: isize, y: isize) -> bool {
+        if x < 0 || x >= self.board.width() as isize || y < 0 || y >= BOARD_HEIGHT as isize {
+            return true;
+        }
+
+        matches!(self.board.blocks[y as usize][x as usize], Block::Occupied(_))
+    }
+
+    // guideline scoring, tetrises and spins which clear lines are "difficult" and
+    // keep the back-to-back chain going, consecutive clearing pieces build up the combo
+    fn score_clear(&mut self, rows: &[usize], spin: Spin) -> Clear {
+        let lines = rows.len();
+        let points = match (spin, lines) {
+            (Spin::None, 0) => 0,
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, _) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, _) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, _) => 1600,
+        };
+
+        if lines == 0 {
+            self.combo = -1;
+            self.stats.score += points;
+            if spin != Spin::None {
+                self.show_callouts(vec![Self::clear_name(lines, spin).to_string()]);
+            }
+            return Clear { lines, spin, back_to_back: false, combo: -1, perfect_clear: false };
+        }
+
+        let difficult = lines == 4 || spin != Spin::None;
+        let back_to_back = difficult && self.back_to_back;
+        self.back_to_back = difficult;
+        self.combo += 1;
+
+        self.stats.score += if back_to_back { points * 3 / 2 } else { points };
+        if self.combo > 0 {
+            self.stats.score += 50 * self.combo as usize;
+        }
+
+        let mut callouts = Vec::new();
+        if back_to_back {
+            callouts.push(format!("B2B {}", Self::clear_name(lines, spin)));
+        } else {
+            callouts.push(Self::clear_name(lines, spin).to_string());
+        }
+        if self.combo > 0 {
+            callouts.push(format!("{} COMBO", self.combo));
+        }
+        self.show_callouts(callouts);
+
+        let perfect_clear = self.board.is_empty_without(rows);
+        if perfect_clear {
+            self.score_perfect_clear(lines, back_to_back);
+        }
+
+        Clear { lines, spin, back_to_back, combo: self.combo, perfect_clear }
+    }
+
+    // guideline perfect clear bonus, awarded on top of the line clear itself
+    fn score_perfect_clear(&mut self, lines: usize, back_to_back: bool) {
+        self.stats.score += match lines {
+            1 => 800,
+            2 => 1200,
+            3 => 1800,
+            _ if back_to_back => 3200,
+            _ => 2000,
+        };
+        self.stats.perfect_clears += 1;
+        self.perfect_clear_elapsed = Some(Duration::from_millis(0));
+    }
+
+    fn clear_name(lines: usize, spin: Spin) -> &'static str {
+        match (spin, lines) {
+            (Spin::None, 1) => "SINGLE",
+            (Spin::None, 2) => "DOUBLE",
+            (Spin::None, 3) => "TRIPLE",
+            (Spin::None, _) => "TETRIS",
+            (Spin::Mini, 0) => "T-SPIN MINI",
+            (Spin::Mini, 1) => "T-SPIN MINI SGL",
+            (Spin::Mini, _) => "T-SPIN MINI DBL",
+            (Spin::Full, 0) => "T-SPIN",
+            (Spin::Full, 1) => "T-SPIN SINGLE",
+            (Spin::Full, 2) => "T-SPIN DOUBLE",
+            (Spin::Full, _) => "T-SPIN TRIPLE",
+        }
+    }
+
+    fn show_callouts(&mut self, callouts: Vec<String>) {
+        self.callouts = callouts;
+        self.callout_elapsed = Duration::from_millis(0);
+    }
+
+    fn is_grounded(&self, seat: usize) -> bool {
+        let active = &self.seats[seat].active;
+        !self.can_fit_tetrimino(seat, active.x, active.y + 1, active.tetrimino.block())
+    }
+
+    // check whether current active tetrimino has been on the ground long enough to fuse with it
+    fn should_fuse_current_tetrimino(&self, seat: usize) -> bool {
+        self.is_grounded(seat) && self.seats[seat].lock_elapsed >= self.rules.lock_delay
+    }
+
+    pub fn initialize_tetrimino(ttype: Type) -> ActiveTetrimino {
+        let (x, y) = match ttype {
+            Type::I => (3, -1),
+            _ => (3, 0)
+        };
+
+        ActiveTetrimino {
+            tetrimino: Tetrimino::new(ttype),
+            x,
+            y,
+            last_kick: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Board {
+    // rows are always as wide as the widest board, only the first `width` cells are played on
+    blocks: [[Block; COOP_BOARD_WIDTH as usize]; BOARD_HEIGHT as usize],
+    width: usize,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Block {
+    Free,
+    Occupied(Rgb),
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Self::with_width(BOARD_WIDTH as usize)
+    }
+
+    /// An empty board of up to `COOP_BOARD_WIDTH` columns.
+    pub fn with_width(width: usize) -> Self {
+        let blocks = [[Block::Free; COOP_BOARD_WIDTH as usize]; BOARD_HEIGHT as usize];
+        Board { blocks, width: width.min(COOP_BOARD_WIDTH as usize) }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Every row from the top down.
+    pub fn blocks(&self) -> impl Iterator<Item = &[Block]> {
+        self.blocks.iter().map(move |row| &row[..self.width])
+    }
+
+    pub fn block(&self, x: usize, y: usize) -> Block {
+        self.blocks[y][x]
+    }
+
+    pub fn is_occupied(&self, x: usize, y: usize) -> bool {
+        matches!(self.blocks[y][x], Block::Occupied(_))
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, block: Block) {
+        self.blocks[y][x] = block;
+    }
+
+    /// Rows given as text down to the bottom of the board, `.` is empty, piece letters are
+    /// blocks of that piece's colour and anything else is garbage.
+    pub fn from_rows<S: AsRef<str>>(rows: &[S]) -> Result<Board, String> {
+        if rows.len() > BOARD_HEIGHT as usize {
+            return Err(format!("a board has at most {} rows", BOARD_HEIGHT));
+        }
+
+        let mut board = Board::new();
+        let top = BOARD_HEIGHT as usize - rows.len();
+        for (i, row) in rows.iter().enumerate() {
+            let row = row.as_ref();
+            if row.chars().count() != BOARD_WIDTH as usize {
+                return Err(format!("a row isn't {} wide: {:?}", BOARD_WIDTH, row));
+            }
+
+            for (x, c) in row.chars().enumerate() {
+                board.blocks[top + i][x] = block_of_letter(c);
+            }
+        }
+
+        Ok(board)
+    }
+
+    /// Every row as text from the top down, the way `from_rows` reads them.
+    pub fn rows(&self) -> Vec<String> {
+        self.blocks()
+            .map(|row| row.iter()
+                .map(|block| match block {
+                    Block::Free => '.',
+                    Block::Occupied(rgb) => Tetrimino::type_of_color(*rgb).map_or('G', Type::letter),
+                })
+                .collect())
+            .collect()
+    }
+
+    /// Pushes the stack up and fills the bottom with garbage rows, one for each hole given from
+    /// the top row down. Returns whether any blocks were pushed off the top.
+    pub fn insert_garbage(&mut self, holes: &[usize]) -> bool {
+        let holes = &holes[holes.len().saturating_sub(BOARD_HEIGHT as usize)..];
+        let lines = holes.len();
+        let overflow = self.blocks[..lines].iter()
+            .any(|row| row.iter().any(|block| matches!(block, Block::Occupied(_))));
+
+        self.blocks.rotate_left(lines);
+        for (row, hole) in self.blocks[BOARD_HEIGHT as usize - lines..].iter_mut().zip(holes) {
+            for (x, block) in row[..self.width].iter_mut().enumerate() {
+                *block = if x == *hole { Block::Free } else { Block::Occupied(GARBAGE_COLOR) };
+            }
+        }
+
+        overflow
+    }
+
+    // check whether given tetrimino block can fit in if it starts at block_x, block_y
+    // e.g if block_x = 4, and block_y = 5, block[0][0] is at the board[5][4]
+    pub fn can_fit(&self, block_x: isize, block_y: isize, block: &[[u8; 4]; 4]) -> bool {
+        for (y, row) in block.iter().enumerate() {
+            for (x, col) in row.iter().enumerate() {
+                if *col == 1 {
+                    // actual co-ordinates on the board
+                    let x = block_x + x as isize;
+                    let y = block_y + y as isize;
+
+                    if x >= self.width as isize || x < 0 || y >= BOARD_HEIGHT as isize || y < 0 {
+                        return false;
+                    } else if let Block::Occupied(_) = self.blocks[y as usize][x as usize] {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Rotates the tetrimino with SRS wall kicks, giving back where it ended up and which kick it took.
+    pub fn try_rotate(&self, tetrimino: &Tetrimino, x: isize, y: isize, clockwise: bool) -> Option<(Tetrimino, isize, isize, usize)> {
+        rotate_with(tetrimino, x, y, clockwise, |x, y, block| self.can_fit(x, y, block))
+    }
+
+    /// Writes the tetrimino into the board and returns the cells it took up, it must fit.
+    pub fn place(&mut self, tetrimino: &Tetrimino, block_x: isize, block_y: isize) -> Vec<(usize, usize)> {
+        let mut cells = Vec::with_capacity(4);
+
+        for (y, row) in tetrimino.block().iter().enumerate() {
+            for (x, col) in row.iter().enumerate() {
+                if *col == 1 {
+                    let x = (block_x + x as isize) as usize;
+                    let y = (block_y + y as isize) as usize;
+                    self.blocks[y][x] = Block::Occupied(tetrimino.color());
+                    cells.push((x, y));
+                }
+            }
+        }
+
+        cells
+    }
+
+    pub fn full_rows(&self) -> Vec<usize> {
+        // doesn't need to iterate through all the boards, can optimise later
+        self.blocks()
+            .enumerate()
+            .filter(|(_, row)| Self::can_erase_row(row))
+            .map(|(y, _)| y)
+            .collect()
+    }
+
+    fn can_erase_row(row: &[Block]) -> bool {
+        for col in row {
+            if let Block::Free = col {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // push down the lines above each row and erase the top line
+    pub fn collapse(&mut self, rows: &[usize]) {
+        for &line in rows {
+            for y in (0..line).rev() {
+                self.blocks[y + 1] = self.blocks[y];
+            }
+            self.blocks[0] = [Block::Free; COOP_BOARD_WIDTH as usize];
+        }
+    }
+
+    /// Whether the row has any garbage in it, rather than only blocks of pieces.
+    pub fn is_garbage_row(&self, y: usize) -> bool {
+        self.blocks[y].contains(&Block::Occupied(GARBAGE_COLOR))
+    }
+
+    // whether the board would be empty once the given rows are cleared
+    fn is_empty_without(&self, rows: &[usize]) -> bool {
+        self.blocks.iter()
+            .enumerate()
+            .filter(|(y, _)| !rows.contains(y))
+            .all(|(_, row)| row.iter().all(|block| matches!(block, Block::Free)))
+    }
+}
+
+// saved as its rows of text, which keep the colours of the blocks
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rows().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<String>::deserialize(deserializer)?;
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if rows.len() != BOARD_HEIGHT as usize || width > COOP_BOARD_WIDTH as usize || rows.iter().any(|row| row.chars().count() != width) {
+            return Err(de::Error::custom(format!("a board is {} rows of the same width", BOARD_HEIGHT)));
+        }
+
+        let mut board = Board::with_width(width);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                board.blocks[y][x] = block_of_letter(c);
+            }
+        }
+        Ok(board)
+    }
+}
+
+// `.` is empty, piece letters are blocks of that piece's colour and anything else is garbage
+fn block_of_letter(c: char) -> Block {
+    match c {
+        '.' | ' ' => Block::Free,
+        _ => match Type::from_letter(c.to_ascii_uppercase()) {
+            Some(ttype) => Block::Occupied(Tetrimino::color_of(ttype)),
+            None => Block::Occupied(GARBAGE_COLOR),
+        },
+    }
+}
+
+// SRS rotation against any check of whether the piece fits, co-op also checks the other piece
+fn rotate_with<F>(tetrimino: &Tetrimino, x: isize, y: isize, clockwise: bool, fits: F) -> Option<(Tetrimino, isize, isize, usize)>
+    where F: Fn(isize, isize, &[[u8; 4]; 4]) -> bool
+{
+    let rotated = if clockwise { tetrimino.rotate_clockwise() } else { tetrimino.rotate_counter_clockwise() };
+
+    tetrimino.kicks(rotated.state)
+        .iter()
+        .enumerate()
+        .map(|(kick, (dx, dy))| (kick, x + dx, y + dy))
+        .find(|(_, x, y)| fits(*x, *y, rotated.block()))
+        .map(|(kick, x, y)| (rotated, x, y, kick))
+}
+
+/// Where the filled cells of a piece's grid are on the board.
+pub fn block_cells(block_x: isize, block_y: isize, block: &[[u8; 4]; 4]) -> Vec<(isize, isize)> {
+    let mut cells = Vec::with_capacity(4);
+    for (y, row) in block.iter().enumerate() {
+        for (x, col) in row.iter().enumerate() {
+            if *col == 1 {
+                cells.push((block_x + x as isize, block_y + y as isize));
+            }
+        }
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTTOM: usize = BOARD_HEIGHT as usize - 1;
+
+    // a stray block up top so none of the clears are perfect clears
+    fn engine() -> Engine {
+        let mut engine = Engine::new(Rules::default());
+        engine.board = Board::from_rows(&["X.........", "..........", "..........", "..........", ".........."]).unwrap();
+        engine
+    }
+
+    fn rows(lines: usize) -> Vec<usize> {
+        (BOTTOM + 1 - lines..=BOTTOM).collect()
+    }
+
+    #[test]
+    fn consecutive_clears_build_a_combo() {
+        let mut engine = engine();
+        assert_eq!(engine.score_clear(&rows(1), Spin::None).combo, 0);
+        assert_eq!(engine.score_clear(&rows(2), Spin::None).combo, 1);
+        assert_eq!(engine.callouts(), ["DOUBLE", "1 COMBO"]);
+        assert_eq!(engine.score_clear(&rows(3), Spin::None).combo, 2);
+        assert_eq!(engine.stats().score, 100 + 300 + 50 + 500 + 100);
+
+        // a piece which doesn't clear anything ends it
+        assert_eq!(engine.score_clear(&[], Spin::None).combo, -1);
+        engine.score_clear(&rows(1), Spin::None);
+        assert_eq!(engine.combo(), 0);
+        assert_eq!(engine.stats().score, 1050 + 100);
+    }
+
+    #[test]
+    fn difficult_clears_in_a_row_are_back_to_back() {
+        let mut engine = engine();
+        let mut clear = |lines, spin| {
+            let clear = engine.score_clear(&rows(lines), spin);
+            engine.score_clear(&[], Spin::None);
+            clear.back_to_back
+        };
+
+        assert!(!clear(4, Spin::None));
+        assert!(clear(4, Spin::None));
+        assert!(clear(2, Spin::Full));
+        assert!(clear(1, Spin::Mini));
+
+        // an ordinary clear breaks the chain, a piece which doesn't clear anything doesn't
+        assert!(!clear(1, Spin::None));
+        assert!(!clear(4, Spin::None));
+        assert!(engine.back_to_back());
+        assert_eq!(engine.stats().score, 800 + 1200 + 1800 + 300 + 100 + 800);
+
+        engine.score_clear(&rows(4), Spin::None);
+        assert_eq!(engine.callouts(), ["B2B TETRIS"]);
+    }
+
+    #[test]
+    fn spins_score_without_clearing_lines() {
+        let mut engine = engine();
+        engine.score_clear(&[], Spin::Full);
+        engine.score_clear(&[], Spin::Mini);
+        assert_eq!(engine.stats().score, 400 + 100);
+        assert_eq!(engine.callouts(), ["T-SPIN MINI"]);
+        assert!(!engine.back_to_back());
+    }
+
+    #[test]
+    fn clearing_the_whole_board_scores_a_perfect_clear() {
+        let mut engine = engine();
+        engine.board = Board::from_rows(&["XXXXXXXXXX"; 4]).unwrap();
+        assert!(!engine.score_clear(&rows(1), Spin::None).perfect_clear);
+        assert!(engine.score_clear(&rows(4), Spin::None).perfect_clear);
+        assert!(engine.score_clear(&rows(4), Spin::None).perfect_clear);
+        assert_eq!(engine.stats().score, 100 + 800 + 50 + 2000 + 1200 + 100 + 3200);
+        assert_eq!(engine.stats().perfect_clears, 2);
+
+        engine.board = Board::from_rows(&["XXXXXXXXXX"; 2]).unwrap();
+        engine.score_clear(&[], Spin::None);
+        engine.score_clear(&rows(2), Spin::None);
+        assert_eq!(engine.stats().score, 7450 + 300 + 1200);
+
+        // the stray block is still there
+        let mut engine = self::engine();
+        assert!(!engine.score_clear(&rows(2), Spin::None).perfect_clear);
+        assert_eq!(engine.stats().perfect_clears, 0);
+    }
+
+    #[test]
+    fn a_perfect_clear_is_scored_when_the_piece_locks() {
+        let puzzle = Puzzle {
+            name: "pc".to_string(),
+            board: vec!["XXXXXXXX..".to_string(), "XXXXXXXX..".to_string()],
+            queue: "OO".to_string(),
+            hold: None,
+            goal: Goal::PerfectClear,
+        };
+        let mut engine = Engine::with_puzzle(Rules::default(), &puzzle).unwrap();
+        for _ in 0..5 {
+            engine.input(Input::Right);
+        }
+        engine.input(Input::HardDrop);
+
+        assert_eq!(engine.stats().score, 300 + 1200);
+        assert_eq!(engine.stats().perfect_clears, 1);
+    }
+
+    // shuffles a grounded piece back and forth just inside the lock delay until it locks
+    fn moves_before_locking(engine: &mut Engine) -> usize {
+        let pieces = engine.stats().pieces;
+        for moves in 0..100 {
+            engine.tick(Duration::from_millis(400));
+            if engine.stats().pieces > pieces {
+                return moves;
+            }
+            engine.input(if moves % 2 == 0 { Input::Left } else { Input::Right });
+        }
+        panic!("the piece never locked");
+    }
+
+    #[test]
+    fn the_lock_delay_is_only_put_off_fifteen_times() {
+        let mut engine = Engine::new(Rules::default());
+        for _ in 0..20 {
+            engine.input(Input::Left);
+            engine.input(Input::Right);
+        }
+        engine.input(Input::SonicDrop);
+
+        // the last move doesn't reset it, the next tick locks the piece
+        assert_eq!(moves_before_locking(&mut engine), MAX_LOCK_RESETS + 1);
+    }
+
+    #[test]
+    fn getting_lower_gives_the_lock_resets_back() {
+        let puzzle = Puzzle {
+            name: "step".to_string(),
+            board: vec!["XXXXXX....".to_string()],
+            queue: "OO".to_string(),
+            hold: None,
+            goal: Goal::PerfectClear,
+        };
+        let mut engine = Engine::with_puzzle(Rules::default(), &puzzle).unwrap();
+        engine.input(Input::SonicDrop);
+        for _ in 0..MAX_LOCK_RESETS {
+            engine.input(Input::Left);
+            engine.input(Input::Right);
+        }
+
+        // off the step and down to the floor
+        for _ in 0..3 {
+            engine.input(Input::Right);
+        }
+        engine.input(Input::SonicDrop);
+        assert_eq!(engine.stats().pieces, 0);
+        assert_eq!(moves_before_locking(&mut engine), MAX_LOCK_RESETS + 1);
+    }
+
+    fn player_cells(engine: &Engine, player: usize) -> Vec<(isize, isize)> {
+        let active = engine.player_active(player).unwrap();
+        block_cells(active.x, active.y, active.tetrimino.block())
+    }
+
+    #[test]
+    fn coop_pieces_come_in_over_each_half() {
+        let engine = Engine::with_coop(Rules::default(), 4);
+        assert_eq!(engine.players(), 2);
+        assert!(player_cells(&engine, 0).iter().all(|&(x, _)| x < BOARD_WIDTH as isize));
+        assert!(player_cells(&engine, 1).iter().all(|&(x, _)| x >= BOARD_WIDTH as isize));
+    }
+
+    #[test]
+    fn coop_pieces_block_each_other_but_not_the_edge() {
+        let mut engine = Engine::with_coop(Rules::default(), 4);
+        for _ in 0..COOP_BOARD_WIDTH {
+            engine.player_input(0, Input::Right);
+            engine.player_input(1, Input::Right);
+        }
+        assert_eq!(player_cells(&engine, 1).iter().map(|&(x, _)| x).max(), Some(COOP_BOARD_WIDTH as isize - 1));
+
+        // the left piece follows over to the right half until it runs into the other one
+        for _ in 0..COOP_BOARD_WIDTH {
+            engine.player_input(0, Input::Right);
+        }
+        let (left, right) = (player_cells(&engine, 0), player_cells(&engine, 1));
+        assert!(left.iter().all(|cell| !right.contains(cell)));
+        assert!(left.iter().any(|&(x, y)| right.contains(&(x + 1, y))));
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Board, Input, Rules};
+use crate::game::movegen::{find_path, spawn_placements, Placement};
+use crate::game::random::Rng;
+use crate::game::tetrimino::{Tetrimino, Type};
+
+/// Compares the keys pressed for each piece against the fewest which would have put it in the same place.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Finesse {
+    pub faults: usize,
+    pub last_fault: Option<Fault>,
+
+    // every key pressed for the current piece
+    inputs: Vec<Input>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Fault {
+    pub used: Vec<Input>,
+    pub optimal: Vec<Input>,
+}
+
+impl Finesse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&mut self, input: Input) {
+        match input {
+            Input::Hold => self.inputs.clear(),
+            _ => self.inputs.push(input),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.inputs.clear();
+    }
+
+    /// Checks the piece which is about to lock on the board, true if it was a fault.
+    pub(crate) fn check(&mut self, board: &Board, tetrimino: Tetrimino, x: isize, y: isize, rules: Rules) -> bool {
+        let placed = Placement { hold: false, tetrimino, x, y, inputs: Vec::new() };
+        let used = std::mem::take(&mut self.inputs);
+
+        let optimal = match find_path(board, &placed, rules) {
+            Some(optimal) => optimal,
+            None => return false,
+        };
+
+        // drops are about speed rather than finesse, only shifts and rotations count
+        if movements(&used) <= movements(&optimal) {
+            return false;
+        }
+
+        self.faults += 1;
+        self.last_fault = Some(Fault { used, optimal });
+        true
+    }
+}
+
+fn movements(inputs: &[Input]) -> usize {
+    inputs.iter()
+        .filter(|input| matches!(input, Input::Left | Input::Right | Input::RotateClockwise | Input::RotateCounterClockwise))
+        .count()
+}
+
+/// Drill mode, where every piece gets a random spot on an empty board to be put into.
+#[derive(Serialize, Deserialize)]
+pub struct Drill {
+    pub target: Option<Placement>,
+    pub hits: usize,
+    pub misses: usize,
+    rng: Rng,
+}
+
+impl Drill {
+    pub fn new(rng: Rng) -> Self {
+        Drill { target: None, hits: 0, misses: 0, rng }
+    }
+
+    pub(crate) fn pick_target(&mut self, ttype: Type, rules: Rules) {
+        let mut placements = spawn_placements(&Board::new(), ttype, rules);
+        let pick = self.rng.below(placements.len());
+        self.target = Some(placements.swap_remove(pick));
+    }
+
+    pub(crate) fn check(&mut self, tetrimino: Tetrimino, x: isize, y: isize) {
+        let placed = Placement { hold: false, tetrimino, x, y, inputs: Vec::new() };
+
+        match &self.target {
+            Some(target) if target.cells() == placed.cells() => self.hits += 1,
+            _ => self.misses += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::Engine;
+    use crate::game::puzzle::{Goal, Puzzle};
+
+    fn engine() -> Engine {
+        let puzzle = Puzzle {
+            name: "finesse".to_string(),
+            board: Vec::new(),
+            queue: "TTTT".to_string(),
+            hold: None,
+            goal: Goal::PerfectClear,
+        };
+        let mut engine = Engine::with_puzzle(Rules::default(), &puzzle).unwrap();
+        engine.enable_finesse();
+        engine
+    }
+
+    fn play(engine: &mut Engine, inputs: &[Input]) -> usize {
+        for &input in inputs {
+            engine.input(input);
+        }
+        engine.tick(Rules::default().entry_delay);
+        engine.finesse().unwrap().faults
+    }
+
+    #[test]
+    fn the_shortest_path_is_never_a_fault() {
+        let mut engine = engine();
+        for placement in spawn_placements(&Board::new(), Type::T, Rules::default()).iter().take(3) {
+            let inputs = find_path(&Board::new(), placement, Rules::default()).unwrap();
+            assert_eq!(play(&mut engine, &inputs), 0, "{:?}", inputs);
+        }
+    }
+
+    #[test]
+    fn extra_shifts_and_rotations_are_faults() {
+        let mut engine = engine();
+        let turned_around = [Input::RotateClockwise, Input::RotateClockwise, Input::RotateClockwise, Input::RotateClockwise, Input::HardDrop];
+        assert_eq!(play(&mut engine, &turned_around), 1);
+        assert!(engine.finesse().unwrap().last_fault.as_ref().unwrap().optimal.iter().all(|&input| movements(&[input]) == 0));
+
+        // drops don't count
+        assert_eq!(play(&mut engine, &[Input::Left, Input::SoftDrop, Input::SonicDrop, Input::HardDrop]), 1);
+        assert_eq!(play(&mut engine, &[Input::Left, Input::Left, Input::Right, Input::HardDrop]), 2);
+
+        let fault = engine.finesse().unwrap().last_fault.as_ref().unwrap();
+        assert_eq!(movements(&fault.used), 3);
+        assert_eq!(movements(&fault.optimal), 1);
+    }
+}
@@ -0,0 +1,552 @@
+use std::iter;
+use std::time::Duration;
+
+use crate::game::engine::{block_cells, Block, Board, Engine, Mode, BOARD_HEIGHT, GARBAGE_COLOR};
+use crate::game::replay::Replay;
+use crate::game::snapshot::Piece;
+use crate::game::tetrimino::{Tetrimino, Type};
+
+const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// the field is 23 rows with the garbage row under them, which rises into the field
+const WIDTH: usize = 10;
+const ROWS: usize = 24;
+const CELLS: usize = WIDTH * ROWS;
+
+// the field goes higher than the board, these rows are above its top
+const ABOVE: usize = ROWS - 1 - BOARD_HEIGHT as usize;
+
+const GRAY: u8 = 8;
+
+// a field which is the same as the page before is written as this run
+const UNCHANGED: u32 = 8 * CELLS as u32 + CELLS as u32 - 1;
+
+// comments are printable ascii from the space on, packed four to five digits
+const COMMENT_BASE: u32 = 96;
+const MAX_COMMENT: usize = 4095;
+
+// blocks by fumen's codes, rows from the top down
+type Field = [[u8; WIDTH]; ROWS];
+
+/// One page of a fumen, a board with the piece being placed on it and a comment.
+#[derive(Clone)]
+pub struct Page {
+    field: Field,
+
+    /// The piece as it would be on the board.
+    pub piece: Option<Piece>,
+    pub comment: String,
+}
+
+impl Page {
+    /// The first ten columns of the board, anything wider doesn't fit in a fumen.
+    pub fn new(board: &Board, piece: Option<Piece>, comment: &str) -> Self {
+        let mut field = [[0; WIDTH]; ROWS];
+        for (y, row) in board.blocks().enumerate() {
+            for (x, block) in row.iter().take(WIDTH).enumerate() {
+                field[ABOVE + y][x] = match block {
+                    Block::Free => 0,
+                    Block::Occupied(rgb) => Tetrimino::type_of_color(*rgb).map_or(GRAY, code),
+                };
+            }
+        }
+
+        Page { field, piece, comment: comment.to_string() }
+    }
+
+    /// The engine's board with its falling piece.
+    pub fn from_engine(engine: &Engine) -> Self {
+        let piece = engine.active().map(|active| Piece {
+            ttype: active.tetrimino.ttype,
+            x: active.x,
+            y: active.y,
+            state: active.tetrimino.state,
+        });
+        Page::new(engine.board(), piece, "")
+    }
+
+    /// The field as a board, the garbage row under the field isn't part of it.
+    pub fn board(&self) -> Result<Board, String> {
+        if self.field[..ABOVE].iter().flatten().any(|block| *block != 0) {
+            return Err("the fumen has blocks above the top of the board".to_string());
+        }
+
+        let mut board = Board::new();
+        for (y, row) in self.field[ABOVE..ROWS - 1].iter().enumerate() {
+            for (x, block) in row.iter().enumerate() {
+                board.set(x, y, match *block {
+                    0 => Block::Free,
+                    block => Block::Occupied(type_of(block).map_or(GARBAGE_COLOR, Tetrimino::color_of)),
+                });
+            }
+        }
+
+        Ok(board)
+    }
+}
+
+/// Reads a v115 fumen, the `v115@` can have a link in front of it.
+pub fn decode(data: &str) -> Result<Vec<Page>, String> {
+    let body = match data.trim().split_once("115@") {
+        Some((prefix, body)) if prefix.ends_with(['v', 'm', 'd']) => body,
+        _ => return Err("only v115 fumen can be read".to_string()),
+    };
+    let values = body.chars()
+        .filter(|c| *c != '?')
+        .map(|c| ALPHABET.find(c).map(|value| value as u32).ok_or_else(|| format!("the fumen has a stray {:?}", c)))
+        .collect::<Result<Vec<u32>, String>>()?;
+    let mut digits = Digits { values, next: 0 };
+
+    let mut pages = Vec::new();
+    let mut field = [[0; WIDTH]; ROWS];
+    let mut comment = String::new();
+    let mut repeat = 0;
+
+    while digits.next < digits.values.len() {
+        // a page with the same field as the one before it can be followed by more of them
+        if repeat > 0 {
+            repeat -= 1;
+        } else if !read_field(&mut digits, &mut field)? {
+            repeat = digits.poll(1)?;
+        }
+
+        let mut action = digits.poll(3)?;
+        let piece = action % 8;
+        action /= 8;
+        let rotation = action % 4;
+        action /= 4;
+        let position = action % CELLS as u32;
+        action /= CELLS as u32;
+        let rise = action & 1 != 0;
+        let mirror = action & 2 != 0;
+        let has_comment = action & 8 != 0;
+        let lock = action & 16 == 0;
+
+        // the comment carries on from page to page until there's another
+        if has_comment {
+            comment = read_comment(&mut digits)?;
+        }
+
+        let piece = type_of(piece as u8).map(|ttype| from_position(ttype, rotation, position));
+        pages.push(Page { field, piece, comment: comment.clone() });
+
+        if lock {
+            lock_piece(&mut field, piece.as_ref());
+            if rise {
+                field.rotate_left(1);
+                field[ROWS - 1] = [0; WIDTH];
+            }
+            if mirror {
+                field[..ROWS - 1].iter_mut().for_each(|row| row.reverse());
+            }
+        }
+    }
+
+    if pages.is_empty() {
+        return Err("the fumen has no pages".to_string());
+    }
+    Ok(pages)
+}
+
+/// Writes the pages as a v115 fumen, each page's piece is locked before the next one.
+pub fn encode(pages: &[Page]) -> String {
+    let mut values = Vec::new();
+    let mut field = [[0; WIDTH]; ROWS];
+    let mut comment = "";
+
+    // where the count of unchanged pages is, while there's a run of them
+    let mut repeat: Option<usize> = None;
+
+    for (i, page) in pages.iter().enumerate() {
+        let changed = write_field(&mut values, &field, &page.field);
+        match repeat {
+            _ if changed => repeat = None,
+            Some(at) if values[at] < 63 => {
+                values.truncate(values.len() - 2);
+                values[at] += 1;
+            }
+            _ => {
+                values.push(0);
+                repeat = Some(values.len() - 1);
+            }
+        }
+
+        let has_comment = page.comment != comment;
+        let (piece, rotation, position) = match &page.piece {
+            Some(piece) => (code(piece.ttype), rotation(piece.state), to_position(piece)),
+            None => (0, 0, 0),
+        };
+        let mut action = 0;
+        action = action * 2 + has_comment as u32;
+        action = action * 2 + (i == 0) as u32;
+        action *= 4;
+        action = action * CELLS as u32 + position;
+        action = action * 4 + rotation;
+        action = action * 8 + piece as u32;
+        push(&mut values, action, 3);
+
+        if has_comment {
+            write_comment(&mut values, &page.comment);
+            comment = &page.comment;
+        }
+
+        field = page.field;
+        lock_piece(&mut field, page.piece.as_ref());
+    }
+
+    // fumen breaks the text up with a ? after the first 42 characters and every 47 after that
+    let text: Vec<char> = values.iter().map(|value| ALPHABET.as_bytes()[*value as usize] as char).collect();
+    let (head, tail) = text.split_at(text.len().min(42));
+    let chunks: Vec<String> = iter::once(head)
+        .chain(tail.chunks(47))
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+    format!("v115@{}", chunks.join("?"))
+}
+
+/// Plays the replay again and makes a page of every piece it locked, on the board it locked on.
+/// The last page is the board it ended on.
+pub fn replay_pages(replay: &Replay) -> Result<Vec<Page>, String> {
+    // dig boards start with rows the replay doesn't have, and puzzles aren't seeded
+    if !matches!(replay.mode, Mode::Marathon | Mode::Sprint | Mode::Ultra) {
+        return Err(format!("a {:?} replay can't be played again", replay.mode).to_lowercase());
+    }
+
+    let mut engine = Engine::with_seed(replay.rules, replay.mode, replay.seed);
+    let frame = Duration::from_millis(replay.frame.max(1));
+    let end = replay.finish.map(|finish| finish.time)
+        .or_else(|| replay.inputs.last().map(|event| event.at))
+        .unwrap_or(0);
+
+    let mut pages = Vec::new();
+    let mut inputs = replay.inputs.iter().peekable();
+    while !engine.is_over() && engine.played().as_millis() as u64 <= end {
+        let now = engine.played().as_millis() as u64;
+        while let Some(event) = inputs.next_if(|event| event.at <= now) {
+            step(&mut engine, &mut pages, |engine| engine.input(event.input));
+        }
+        step(&mut engine, &mut pages, |engine| engine.tick(frame));
+    }
+
+    pages.push(Page::from_engine(&engine));
+    Ok(pages)
+}
+
+// makes a page when the step locks the falling piece, it locked where it would have dropped to
+fn step<F: FnOnce(&mut Engine)>(engine: &mut Engine, pages: &mut Vec<Page>, act: F) {
+    let pieces = engine.stats().pieces;
+    let before = Page::from_engine(engine);
+    let board = engine.board().clone();
+
+    act(engine);
+
+    if let (true, Some(mut piece)) = (engine.stats().pieces > pieces, before.piece) {
+        let block = Tetrimino::block_of(piece.ttype, piece.state % 4);
+        while board.can_fit(piece.x, piece.y + 1, block) {
+            piece.y += 1;
+        }
+        pages.push(Page { piece: Some(piece), ..before });
+    }
+}
+
+// base 64 digits, least significant first
+struct Digits {
+    values: Vec<u32>,
+    next: usize,
+}
+
+impl Digits {
+    fn poll(&mut self, count: usize) -> Result<u32, String> {
+        let digits = self.values.get(self.next..self.next + count).ok_or("the fumen is cut short")?;
+        self.next += count;
+        Ok(digits.iter().rev().fold(0, |value, digit| value * 64 + digit))
+    }
+}
+
+fn push(values: &mut Vec<u32>, mut value: u32, count: usize) {
+    for _ in 0..count {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+// fields are written as runs of the difference to the field before, returns whether it changed
+fn read_field(digits: &mut Digits, field: &mut Field) -> Result<bool, String> {
+    let mut changed = true;
+    let mut index = 0;
+
+    while index < CELLS {
+        let run = digits.poll(2)?;
+        if run == UNCHANGED {
+            changed = false;
+        }
+
+        let diff = (run / CELLS as u32) as i32 - 8;
+        for _ in 0..=run % CELLS as u32 {
+            let block = field.get_mut(index / WIDTH).ok_or("the fumen's field runs over")?;
+            let block = &mut block[index % WIDTH];
+            let value = *block as i32 + diff;
+            if !(0..=GRAY as i32).contains(&value) {
+                return Err("the fumen has a block which isn't a piece".to_string());
+            }
+            *block = value as u8;
+            index += 1;
+        }
+    }
+
+    Ok(changed)
+}
+
+fn write_field(values: &mut Vec<u32>, before: &Field, field: &Field) -> bool {
+    let diffs: Vec<u32> = before.iter().flatten()
+        .zip(field.iter().flatten())
+        .map(|(before, block)| *block as u32 + 8 - *before as u32)
+        .collect();
+
+    let mut changed = false;
+    let mut start = 0;
+    for i in 1..=CELLS {
+        if i == CELLS || diffs[i] != diffs[start] {
+            let run = diffs[start] * CELLS as u32 + (i - start - 1) as u32;
+            changed |= run != UNCHANGED;
+            push(values, run, 2);
+            start = i;
+        }
+    }
+
+    changed
+}
+
+fn read_comment(digits: &mut Digits) -> Result<String, String> {
+    let length = digits.poll(2)? as usize;
+    let mut escaped = String::new();
+    for _ in 0..length.div_ceil(4) {
+        let mut value = digits.poll(5)?;
+        for _ in 0..4 {
+            escaped.push(char::from(b' ' + (value % COMMENT_BASE) as u8));
+            value /= COMMENT_BASE;
+        }
+    }
+    escaped.truncate(length);
+
+    Ok(unescape(&escaped))
+}
+
+fn write_comment(values: &mut Vec<u32>, comment: &str) {
+    let mut escaped = escape(comment);
+    escaped.truncate(MAX_COMMENT);
+
+    push(values, escaped.len() as u32, 2);
+    for chunk in escaped.as_bytes().chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, c| value * COMMENT_BASE + (c - b' ') as u32);
+        push(values, value, 5);
+    }
+}
+
+// comments are kept the way javascript's escape writes them
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let hex = |from: usize, length: usize| chars.get(from..from + length)
+        .filter(|digits| digits.iter().all(char::is_ascii_hexdigit))
+        .and_then(|digits| u16::from_str_radix(&digits.iter().collect::<String>(), 16).ok());
+
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (unit, length) = match (chars[i], chars.get(i + 1)) {
+            ('%', Some('u')) => hex(i + 2, 4).map_or((chars[i] as u16, 1), |unit| (unit, 6)),
+            ('%', _) => hex(i + 1, 2).map_or((chars[i] as u16, 1), |unit| (unit, 3)),
+            (c, _) => (c as u16, 1),
+        };
+        units.push(unit);
+        i += length;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+// places the page's piece then clears the lines like fumen does for the next page
+fn lock_piece(field: &mut Field, piece: Option<&Piece>) {
+    if let Some(piece) = piece {
+        for (x, y) in block_cells(piece.x, piece.y, Tetrimino::block_of(piece.ttype, piece.state % 4)) {
+            let row = y + ABOVE as isize;
+            if (0..WIDTH as isize).contains(&x) && (0..ROWS as isize - 1).contains(&row) {
+                field[row as usize][x as usize] = code(piece.ttype);
+            }
+        }
+    }
+
+    let kept: Vec<[u8; WIDTH]> = field[..ROWS - 1].iter().filter(|row| row.contains(&0)).copied().collect();
+    let cleared = ROWS - 1 - kept.len();
+    field[..cleared].fill([0; WIDTH]);
+    field[cleared..ROWS - 1].copy_from_slice(&kept);
+}
+
+fn code(ttype: Type) -> u8 {
+    match ttype {
+        Type::I => 1,
+        Type::L => 2,
+        Type::O => 3,
+        Type::Z => 4,
+        Type::T => 5,
+        Type::J => 6,
+        Type::S => 7,
+    }
+}
+
+fn type_of(code: u8) -> Option<Type> {
+    Type::ALL.iter().copied().find(|ttype| self::code(*ttype) == code)
+}
+
+// fumen's rotations start from upside down, the engine's states from spawn
+fn rotation(state: usize) -> u32 {
+    match state % 4 {
+        0 => 2,
+        1 => 1,
+        2 => 0,
+        _ => 3,
+    }
+}
+
+// the piece's cells around its centre with y pointing up, turned to the state
+fn offsets(ttype: Type, state: usize) -> [(isize, isize); 4] {
+    let spawn = match ttype {
+        Type::I => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        Type::T => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        Type::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Type::L => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        Type::J => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        Type::S => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        Type::Z => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+    };
+    spawn.map(|(x, y)| match state % 4 {
+        0 => (x, y),
+        1 => (y, -x),
+        2 => (-x, -y),
+        _ => (-y, x),
+    })
+}
+
+// fumen keeps some pieces one cell away from their centre
+fn correction(ttype: Type, state: usize) -> (isize, isize) {
+    match (ttype, state % 4) {
+        (Type::O, 0) => (0, -1),
+        (Type::O, 2) => (1, 0),
+        (Type::O, 3) => (1, -1),
+        (Type::I, 2) => (1, 0),
+        (Type::I, 3) => (0, -1),
+        (Type::S, 0) => (0, -1),
+        (Type::S, 1) => (-1, 0),
+        (Type::Z, 0) => (0, -1),
+        (Type::Z, 3) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+// the piece from where fumen keeps it, counted along the rows of the field from the top
+fn from_position(ttype: Type, rotation: u32, position: u32) -> Piece {
+    let state = match rotation {
+        0 => 2,
+        1 => 1,
+        2 => 0,
+        _ => 3,
+    };
+    let (dx, dy) = correction(ttype, state);
+    let x = (position as usize % WIDTH) as isize + dx;
+    let up = ROWS as isize - 2 - (position as usize / WIDTH) as isize + dy;
+
+    // lined up with the piece's grid in the engine
+    let cells = offsets(ttype, state).map(|(ox, oy)| (x + ox, BOARD_HEIGHT as isize - 1 - (up + oy)));
+    let grid = block_cells(0, 0, Tetrimino::block_of(ttype, state));
+    Piece {
+        ttype,
+        x: min(cells.iter().map(|cell| cell.0)) - min(grid.iter().map(|cell| cell.0)),
+        y: min(cells.iter().map(|cell| cell.1)) - min(grid.iter().map(|cell| cell.1)),
+        state,
+    }
+}
+
+fn to_position(piece: &Piece) -> u32 {
+    let cells = block_cells(piece.x, piece.y, Tetrimino::block_of(piece.ttype, piece.state % 4));
+    let offsets = offsets(piece.ttype, piece.state);
+    let (dx, dy) = correction(piece.ttype, piece.state);
+
+    let x = min(cells.iter().map(|cell| cell.0)) - min(offsets.iter().map(|offset| offset.0)) - dx;
+    let up = min(cells.iter().map(|cell| BOARD_HEIGHT as isize - 1 - cell.1)) - min(offsets.iter().map(|offset| offset.1)) - dy;
+    let row = ROWS as isize - 2 - up;
+    (row * WIDTH as isize + x).clamp(0, CELLS as isize - 1) as u32
+}
+
+fn min<I: Iterator<Item = isize>>(values: I) -> isize {
+    values.min().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bottom(board: &Board) -> Vec<String> {
+        board.rows().split_off(BOARD_HEIGHT as usize - 2)
+    }
+
+    #[test]
+    fn an_empty_field_is_read_and_written() {
+        let pages = decode("v115@vhAAgH").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].piece.is_none());
+        assert!(pages[0].board().unwrap().rows().iter().all(|row| row == ".........."));
+        assert_eq!(encode(&pages), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn garbage_is_read_as_gray_blocks() {
+        let data = "v115@bhI8KeAgH";
+        let pages = decode(data).unwrap();
+        assert_eq!(bottom(&pages[0].board().unwrap()), ["..........", "GGGGGGGGG."]);
+        assert_eq!(encode(&pages), data);
+    }
+
+    #[test]
+    fn a_piece_is_placed_where_fumen_puts_it() {
+        let data = "v115@vhAVQJ";
+        let pages = decode(data).unwrap();
+        let piece = pages[0].piece.unwrap();
+        assert_eq!((piece.ttype, piece.state), (Type::T, 0));
+
+        let mut field = pages[0].field;
+        lock_piece(&mut field, Some(&piece));
+        let locked = Page { field, piece: None, comment: String::new() };
+        assert_eq!(bottom(&locked.board().unwrap()), ["....T.....", "...TTT...."]);
+        assert_eq!(encode(&pages), data);
+    }
+
+    #[test]
+    fn comments_carry_on_to_the_next_page() {
+        let data = "v115@vhAAgWCAouBAA";
+        let pages = decode(data).unwrap();
+        assert_eq!(pages[0].comment, "hi");
+        assert_eq!(encode(&pages), data);
+
+        let page = Page::new(&Board::new(), None, "50% off");
+        let pages = decode(&encode(&[page.clone(), page])).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|page| page.comment == "50% off"));
+    }
+
+    #[test]
+    fn other_versions_are_turned_away() {
+        assert!(decode("v110@vhAAgH").is_err());
+        assert!(decode("v115@vh").is_err());
+    }
+}
@@ -1,470 +1,293 @@
+use std::collections::VecDeque;
 use std::io::{Read, Result, Write};
 use std::thread;
 use std::time::Duration;
 
 use termion::{clear, cursor, style};
-use termion::color::{Bg, Rgb};
 
-use crate::graphics::*;
 use termion::input::TermRead;
 use termion::event::Key;
 use termion::input::Keys;
 use termion::raw::{IntoRawMode, RawTerminal};
-use rand::rngs::ThreadRng;
-use rand::thread_rng;
-use rand::seq::SliceRandom;
-use crate::game::tetrimino::{Tetrimino, Type};
+use crate::bot::Bot;
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Input, Mode, Rules};
+use crate::game::replay::{Ghost, Replay};
+use crate::game::snapshot::Snapshot;
+use crate::game::view::{clock, View, VIEW_HEIGHT, VIEW_WIDTH};
+use crate::net::Broadcast;
 
-const LEFT_PANEL_WIDTH: u16 = 17;
-
-const SCORE_WINDOW_HEIGHT: u16 = 8;
-const HELP_WINDOW_HEIGHT: u16 = 13;
-
-const BOARD_WIDTH: u16 = 10;
-const BOARD_HEIGHT: u16 = 20;
-
-const RIGHT_PANEL_WIDTH: u16 = 12;
-const NEXT_WINDOW_HEIGHT: u16 = 10;
-const HELD_WINDOW_HEIGHT: u16 = 10;
+const FRAME: Duration = Duration::from_millis(50);
 
 pub struct Game<R: Read, W: Write> {
-    x: u16,
-    y: u16,
-    score: usize,
-    lines: usize,
-    board: Board,
     stdin: Keys<R>,
     stdout: W,
-    state: State,
-    current_tetrimino: ActiveTetrimino,
-    next_type: Type,
-    held_type: Option<Type>,
-    can_hold: bool,
-    elapsed: Duration,
-    fall_rate: Duration,
-    generator: SevenGenerator,
-}
+    engine: Engine,
+    view: View,
 
-enum State {
-    Playing,
-    Lost,
-}
+    // when there's a bot it plays instead of the keyboard, one input a frame so it can be watched
+    bot: Option<Box<dyn Bot>>,
+    bot_inputs: VecDeque<Input>,
+
+    // every input is recorded when there's a replay, and a ghost is a replay raced alongside
+    replay: Option<Replay>,
+    ghost: Option<(Ghost, View)>,
 
-struct ActiveTetrimino {
-    tetrimino: Tetrimino,
-
-    // These must be isize because tetrimino's grid can go out of bound
-    // e.g block L has this shape on the left.
-    //
-    // 0 1 0 0      If this block is at the far left corner
-    // 0 1 0 0      the x will be -1, doesn't have a good solution for this yet
-    // 0 1 1 1      And this is why there are a lot of type casts in the code
-    // 0 0 0 0      Not great
-    x: isize,
-    y: isize,
+    // watchers who get a snapshot whenever something changes
+    stream: Option<Broadcast>,
+    last_published: Option<Snapshot>,
+
+    // q was pressed, rather than enter at the end of a puzzle
+    quit: bool,
 }
 
 impl<R: Read, W: Write> Game<R, W> {
     pub fn new(x: u16, y: u16, r: R, w: W) -> Game<R, RawTerminal<W>> {
-        let mut generator = SevenGenerator::new();
-        let current_ttype = generator.next().unwrap();
-        let next_ttype = generator.next().unwrap();
+        Self::with_rules(x, y, r, w, Rules::default())
+    }
+
+    pub fn with_rules(x: u16, y: u16, r: R, w: W, rules: Rules) -> Game<R, RawTerminal<W>> {
+        Self::with_engine(x, y, r, w, Engine::new(rules))
+    }
+
+    pub fn with_engine(x: u16, y: u16, r: R, w: W, engine: Engine) -> Game<R, RawTerminal<W>> {
+        let mut view = View::new(x, y, Controls::single());
+        view.set_hint_key(Some("h"));
+        view.set_finesse_hints(engine.drill().is_some());
 
         Game {
-            x,
-            y,
-            score: 0,
-            lines: 0,
-            board: Board::new(),
             stdin: r.keys(),
             stdout: w.into_raw_mode().unwrap(),
-            state: State::Playing,
-            current_tetrimino: Self::initialize_tetrimino(current_ttype),
-            next_type: next_ttype,
-            held_type: None,
-            can_hold: true,
-            elapsed: Duration::from_millis(0),
-            fall_rate: Duration::from_millis(500),
-            generator,
+            engine,
+            view,
+            bot: None,
+            bot_inputs: VecDeque::new(),
+            replay: None,
+            ghost: None,
+            stream: None,
+            last_published: None,
+            quit: false,
         }
     }
 
-    pub fn start(&mut self) -> Result<()> {
-        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
-
-        'main: loop {
-            if let State::Lost = self.state {
-                thread::sleep(Duration::from_millis(50));
-                if let Some(Ok(Key::Char('q'))) = self.stdin.next() {
-                    break 'main;
-                }
-                continue;
-            }
-
-            thread::sleep(Duration::from_millis(50));
-            self.update(Duration::from_millis(50));
-
-            match self.stdin.next() {
-                Some(Ok(key)) => {
-                    match key {
-                        Key::Char('j') | Key::Left => self.handle_tetrimino_move(-1, 0),
-                        Key::Char('l') | Key::Right => self.handle_tetrimino_move(1, 0),
-                        Key::Char('k') | Key::Down => self.handle_tetrimino_move(0, 1),
-                        Key::Char('x') => {
-                            let new_state = self.current_tetrimino.tetrimino.rotate_clockwise();
-                            if self.can_fit_tetrimino(self.current_tetrimino.x, self.current_tetrimino.y, new_state.block()) {
-                                self.current_tetrimino.tetrimino = new_state;
-                            }
-                        }
-                        Key::Char('z') => {
-                            let new_state = self.current_tetrimino.tetrimino.rotate_counter_clockwise();
-                            if self.can_fit_tetrimino(self.current_tetrimino.x, self.current_tetrimino.y, new_state.block()) {
-                                self.current_tetrimino.tetrimino = new_state;
-                            }
-                        }
-                        Key::Char(' ') => self.drop_current_tetrimino(),
-                        Key::Char('c') => self.try_hold_tetrimino(),
-                        Key::Char('q') => break 'main,
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
 
-            self.try_fuse_with_ground();
-            self.erase_lines();
-            self.check_game_over();
-            self.draw_player_score()?;
-            self.draw_help()?;
-            self.draw_board()?;
-            self.draw_next()?;
-            self.draw_held()?;
-            self.stdout.flush()?;
+    /// Plays another game in the same terminal, like the next puzzle of a pack.
+    pub fn set_engine(&mut self, mut engine: Engine) {
+        if self.engine.finesse().is_some() {
+            engine.enable_finesse();
+        }
+        if engine.drill().is_some() {
+            self.view.set_finesse_hints(true);
         }
-        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
 
-        Ok(())
+        self.engine = engine;
+        self.view.reset();
+        self.bot_inputs.clear();
+        self.quit = false;
     }
 
-    fn update(&mut self, elapsed: Duration) {
-        self.elapsed += elapsed;
+    /// Why the bot stopped the game partway, it can't carry on playing once it has failed.
+    pub fn bot_failure(&self) -> Option<&str> {
+        self.bot.as_ref().and_then(|bot| bot.failure())
+    }
 
-        if self.elapsed >= self.fall_rate {
-            self.elapsed -= self.fall_rate;
-            self.current_tetrimino.y += 1;
-        }
+    /// Whether the last game ended by quitting.
+    pub fn has_quit(&self) -> bool {
+        self.quit
     }
 
-    fn check_game_over(&mut self) {
-        for col in &self.board.blocks[0] {
-            if let Block::Occupied(_) = col {
-                self.state = State::Lost;
-                break;
-            }
-        }
+    pub fn set_animations(&mut self, animations: bool) {
+        self.view.set_animations(animations);
     }
 
-    fn drop_current_tetrimino(&mut self) {
-        for _ in 0..BOARD_HEIGHT {
-            self.handle_tetrimino_move(0, 1);
-        }
+    /// Hints are only allowed when the result doesn't count for anything, they're on by default.
+    pub fn set_hints(&mut self, allowed: bool) {
+        self.view.set_hint_key(if allowed { Some("h") } else { None });
     }
 
-    fn try_hold_tetrimino(&mut self) {
-        if !self.can_hold {
-            return;
-        }
+    pub fn set_bot(&mut self, bot: Box<dyn Bot>) {
+        self.bot = Some(bot);
+    }
 
-        if let Some(current) = self.held_type.take() {
-            self.held_type = Some(self.current_tetrimino.tetrimino.ttype);
-            self.current_tetrimino = Self::initialize_tetrimino(current);
-        } else {
-            self.held_type = Some(self.current_tetrimino.tetrimino.ttype);
-            self.current_tetrimino = Self::initialize_tetrimino(self.next_type);
-            self.next_type = self.generator.next().unwrap();
-        }
+    /// Records the game from here on, it has to be set before the game starts.
+    pub fn record(&mut self) {
+        self.replay = Some(Replay::new(&self.engine, FRAME));
+    }
 
-        self.can_hold = false;
+    /// Records on into a replay of this game which was saved partway through.
+    pub fn set_replay(&mut self, replay: Replay) {
+        self.replay = Some(replay);
     }
 
-    fn handle_tetrimino_move(&mut self, dx: isize, dy: isize) {
-        let new_x = self.current_tetrimino.x as isize + dx;
-        let new_y = self.current_tetrimino.y as isize + dy;
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
 
-        if self.can_fit_tetrimino(new_x, new_y, self.current_tetrimino.tetrimino.block()) {
-            self.current_tetrimino.x = new_x;
-            self.current_tetrimino.y = new_y;
-        }
+    /// Plays the recorded game next to this one, the engine should have the replay's seed and rules.
+    pub fn set_ghost(&mut self, ghost: Ghost) {
+        let mut view = View::new(self.view.x() + VIEW_WIDTH + 1, self.view.y(), Controls::none());
+        view.set_quit_key(None);
+        self.ghost = Some((ghost, view));
     }
 
-    // check whether given tetrimino block can fit in if it starts at block_x, block_y
-    // e.g if block_x = 4, and block_y = 5, block[0][0] is at the board[5][4]
-    fn can_fit_tetrimino(&self, block_x: isize, block_y: isize, block: &[[u8; 4]; 4]) -> bool {
-        for (y, row) in block.iter().enumerate() {
-            for (x, col) in row.iter().enumerate() {
-                if *col == 1 {
-                    // actual co-ordinates on the board
-                    let x = block_x + x as isize;
-                    let y = block_y + y as isize;
-
-                    if x >= BOARD_WIDTH as isize || x < 0 {
-                        return false;
-                    } else if y >= BOARD_HEIGHT as isize || y < 0 {
-                        return false;
-                    } else if let Block::Occupied(_) = self.board.blocks[y as usize][x as usize] {
-                        return false;
-                    }
-                }
-            }
-        }
+    /// Publishes the game to anyone who connects with `tetrust watch`.
+    pub fn set_stream(&mut self, stream: Broadcast) {
+        self.stream = Some(stream);
+    }
 
-        true
+    /// Counts finesse faults, and with hints also shows how each faulted piece should have been placed.
+    pub fn set_finesse(&mut self, hints: bool) {
+        self.engine.enable_finesse();
+        self.view.set_finesse_hints(hints);
     }
 
-    fn try_fuse_with_ground(&mut self) {
-        let tetrimino_block = self.current_tetrimino.tetrimino.block();
-
-        if self.should_fuse_current_tetrimino() {
-            for (y, row) in tetrimino_block.iter().enumerate() {
-                for (x, col) in row.iter().enumerate() {
-                    if *col == 1 {
-                        let rgb = self.current_tetrimino.tetrimino.color();
-                        let x = self.current_tetrimino.x + x as isize;
-                        let y = self.current_tetrimino.y + y as isize;
-                        self.board.blocks[y as usize][x as usize] = Block::Occupied(rgb);
-                    }
-                }
-            }
+    pub fn start(&mut self) -> Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
 
-            self.current_tetrimino = Self::initialize_tetrimino(self.next_type);
-            self.next_type = self.generator.next().unwrap();
-            self.can_hold = true;
-        }
-    }
+        'main: loop {
+            if self.engine.is_over() {
+                thread::sleep(FRAME);
+                self.publish()?;
+
+                // the ghost carries on to show how its game ended
+                if let Some((ghost, _)) = &mut self.ghost {
+                    ghost.advance(ghost.engine().played() + FRAME);
+                    self.draw_ghost()?;
+                    self.stdout.flush()?;
+                }
 
-    // check whether current active tetrimino should be fused with the ground
-    fn should_fuse_current_tetrimino(&self) -> bool {
-        for (y, row) in self.current_tetrimino.tetrimino.block().iter().enumerate() {
-            for (x, square) in row.iter().enumerate() {
-                // if current square is 1 (has block), check if we're at the edge or the block on the next row
-                if *square == 1 {
-                    let x_on_board = self.current_tetrimino.x + x as isize;
-                    let y_on_board = self.current_tetrimino.y + y as isize;
-
-                    if y_on_board == BOARD_HEIGHT as isize - 1 {
-                        return true;
-                    } else if let Block::Occupied(_) = self.board.blocks[y_on_board as usize + 1][x_on_board as usize] {
-                        return true;
+                match self.stdin.next() {
+                    Some(Ok(Key::Char('q'))) => {
+                        self.quit = true;
+                        break 'main;
                     }
+                    Some(Ok(Key::Char('\n'))) if self.engine.mode() == Mode::Puzzle => break 'main,
+                    _ => {}
                 }
+                continue;
             }
-        }
 
-        false
-    }
+            thread::sleep(FRAME);
 
-    fn erase_lines(&mut self) {
-        let mut erasable_lines = Vec::new();
-
-        // doesn't need to iterate through all the boards, can optimise later
-        for (y, row) in self.board.blocks.iter().enumerate() {
-            if Self::can_erase_row(row) {
-                erasable_lines.push(y);
+            if let Some(Ok(key)) = self.stdin.next() {
+                match key {
+                    Key::Char('q') => {
+                        self.quit = true;
+                        break 'main;
+                    }
+                    _ if self.bot.is_some() => {}
+                    Key::Char('h') => self.view.toggle_hints(),
+                    key => {
+                        if let Some(input) = self.view.controls().input(key) {
+                            self.input(input);
+                        }
+                    }
+                }
             }
-        }
-
-        self.lines += erasable_lines.len();
-        self.score += erasable_lines.len() * 10;
 
-        // push down the lines and erase the top line
-        for line in erasable_lines {
-            for y in (0..line).rev() {
-                for x in 0..BOARD_WIDTH {
-                    self.board.blocks[y as usize + 1][x as usize] = self.board.blocks[y as usize][x as usize];
-                }
+            if let Some(input) = self.next_bot_input() {
+                self.input(input);
             }
-            for x in 0..BOARD_WIDTH {
-                self.board.blocks[0][x as usize] = Block::Free;
+            if self.bot_failure().is_some() {
+                break 'main;
             }
-        }
-    }
 
-    fn can_erase_row(row: &[Block; BOARD_WIDTH as usize]) -> bool {
-        for col in row {
-            if let Block::Free = col {
-                return false;
+            self.engine.tick(FRAME);
+            if let Some(replay) = &mut self.replay {
+                replay.finish(&self.engine);
             }
-        }
-
-        true
-    }
-
-    fn draw_player_score(&mut self) -> Result<()> {
-        let (x, y) = (self.x, self.y);
-        create_window(&mut self.stdout, x, y, LEFT_PANEL_WIDTH, SCORE_WINDOW_HEIGHT)?;
-        write!(self.stdout, "{}Score", cursor::Goto(x + 6, y + 2))?;
-        write!(self.stdout, "{}score: {:04} ", cursor::Goto(x + 3, y + 4), self.score)?;
-        write!(self.stdout, "{}lines: {:04} ", cursor::Goto(x + 3, y + 5), self.lines)
-    }
-
-    fn draw_help(&mut self) -> Result<()> {
-        let (x, y) = (self.x, self.y + SCORE_WINDOW_HEIGHT + 1);
-        create_window(&mut self.stdout, x, y, LEFT_PANEL_WIDTH, HELP_WINDOW_HEIGHT)?;
-        write!(self.stdout, "{}Ctrls", cursor::Goto(x + 6, y + 2))?;
-        write!(self.stdout, "{}left   j, ←", cursor::Goto(x + 3, y + 4))?;
-        write!(self.stdout, "{}right  l, →", cursor::Goto(x + 3, y + 5))?;
-        write!(self.stdout, "{}down   k, ↓", cursor::Goto(x + 3, y + 6))?;
-        write!(self.stdout, "{}drop   space", cursor::Goto(x + 3, y + 7))?;
-        write!(self.stdout, "{}rotate x, z", cursor::Goto(x + 3, y + 8))?;
-        write!(self.stdout, "{}hold   c", cursor::Goto(x + 3, y + 9))?;
-        write!(self.stdout, "{}quit   q", cursor::Goto(x + 3, y + 10))
-    }
-
-    fn draw_board(&mut self) -> Result<()> {
-        let (init_x, init_y) = (self.x + LEFT_PANEL_WIDTH + 1, self.y);
-        create_window(&mut self.stdout, init_x, init_y, (BOARD_WIDTH * 2) + 2, BOARD_HEIGHT + 2)?;
-
-        // draw the board
-        let (init_x, init_y) = (init_x + 1, init_y + 1);
-        for (y, row) in self.board.blocks.iter().enumerate() {
-            for (x, col) in row.iter().enumerate() {
-                match col {
-                    Block::Free => write!(self.stdout, "{}{}  ",
-                                          cursor::Goto(init_x + (x * 2) as u16, init_y + y as u16),
-                                          style::Reset)?,
-                    Block::Occupied(rgb) => write!(self.stdout, "{}{}  ",
-                                                   cursor::Goto(init_x + (x * 2) as u16, init_y + y as u16),
-                                                   Bg(*rgb))?
-                }
+            if let Some((ghost, _)) = &mut self.ghost {
+                ghost.advance(self.engine.played());
             }
-        }
-
-        match self.state {
-            State::Lost => Ok(()),
 
-            // draw current tetrimino, doesn't need to care about bounds, we can't even move outside of bound!
-            State::Playing => self.draw_tetrimino(init_x as isize + self.current_tetrimino.x * 2,
-                                                  init_y as isize + self.current_tetrimino.y,
-                                                  65535, 65535,
-                                                  self.current_tetrimino.tetrimino.ttype,
-                                                  self.current_tetrimino.tetrimino.state)
+            self.view.draw(&mut self.stdout, &self.engine)?;
+            self.draw_ghost()?;
+            self.stdout.flush()?;
+            self.publish()?;
         }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+        self.stdout.flush()
     }
 
-    fn draw_next(&mut self) -> Result<()> {
-        let (x, y) = (self.x + LEFT_PANEL_WIDTH + BOARD_WIDTH * 2 + 4, self.y);
-        create_window(&mut self.stdout, x, y, RIGHT_PANEL_WIDTH, NEXT_WINDOW_HEIGHT)?;
-        write!(self.stdout, "{}Next", cursor::Goto(x + 4, y + 2))?;
-
-        for i in 0..4 {
-            write!(self.stdout, "{}        ", cursor::Goto(x + 2, y + i + 4))?; // clear first
+    fn input(&mut self, input: Input) {
+        if let Some(replay) = &mut self.replay {
+            replay.record(&self.engine, input);
         }
+        self.engine.input(input);
+    }
 
-        self.draw_tetrimino(x as isize + 2, y as isize + 4, x as isize + 9, y as isize + 9, self.next_type, 0)?;
+    // the ghost's board goes next to the live one, with how far ahead or behind it is underneath
+    fn draw_ghost(&mut self) -> Result<()> {
+        let (ghost, view) = match &mut self.ghost {
+            Some(ghost) => ghost,
+            None => return Ok(()),
+        };
+        view.draw(&mut self.stdout, ghost.engine())?;
 
-        Ok(())
+        let status = ghost_status(&self.engine, ghost);
+        write!(self.stdout, "{}{}{}ghost: {}", cursor::Goto(self.view.x(), self.view.y() + VIEW_HEIGHT), style::Reset, clear::CurrentLine, status)
     }
 
-    fn draw_held(&mut self) -> Result<()> {
-        let (x, y) = (self.x + LEFT_PANEL_WIDTH + BOARD_WIDTH * 2 + 4, self.y + NEXT_WINDOW_HEIGHT + 2);
-        create_window(&mut self.stdout, x, y, RIGHT_PANEL_WIDTH, HELD_WINDOW_HEIGHT)?;
-        write!(self.stdout, "{}Held", cursor::Goto(x + 4, y + 2))?;
-
-        for i in 0..4 {
-            write!(self.stdout, "{}        ", cursor::Goto(x + 2, y + i + 4))?; // clear first
-        }
+    // watchers who just joined get the current state straight away, everyone else only when
+    // it changes or a heartbeat is due
+    fn publish(&mut self) -> Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
 
-        if let Some(held) = self.held_type {
-            self.draw_tetrimino(x as isize + 2, y as isize + 4, x as isize + 9, y as isize + 9, held, 0)?;
+        let joined = stream.accept();
+        let snapshot = self.engine.snapshot();
+        if joined || stream.needs_heartbeat() || self.last_published.as_ref() != Some(&snapshot) {
+            stream.send(&snapshot)?;
+            self.last_published = Some(snapshot);
         }
 
         Ok(())
     }
 
-    fn draw_tetrimino(&mut self, x: isize, y: isize, bound_x: isize, bound_y: isize, ttype: Type, state: usize) -> Result<()> {
-        let block = Tetrimino::block_of(ttype, state);
-        let color = Tetrimino::color_of(ttype);
+    fn next_bot_input(&mut self) -> Option<Input> {
+        let bot = self.bot.as_mut()?;
 
-        for (yi, row) in block.iter().enumerate() {
-            for (xi, col) in row.iter().enumerate() {
-                let x = x + xi as isize * 2;
-                let y = y + yi as isize;
-
-                if *col == 1 && x >= 0 && x < bound_x && y >= 0 && y <= bound_y {
-                    write!(self.stdout, "{}{}  {}", cursor::Goto(x as u16, y as u16), Bg(color), style::Reset)?;
-                }
+        if self.bot_inputs.is_empty() && self.engine.active().is_some() {
+            match bot.suggest(&self.engine) {
+                Some(placement) => self.bot_inputs.extend(placement.inputs),
+                None if bot.failure().is_some() => {}
+                None => self.bot_inputs.push_back(Input::HardDrop),
             }
         }
 
-        write!(self.stdout, "{}", style::Reset)
-    }
-
-    fn initialize_tetrimino(ttype: Type) -> ActiveTetrimino {
-        let (x, y) = match ttype {
-            Type::I => (3, -1),
-            _ => (3, 0)
-        };
-
-        let current_tetrimino = ActiveTetrimino {
-            tetrimino: Tetrimino::new(ttype),
-            x,
-            y,
-        };
-
-        current_tetrimino
-    }
-}
-
-pub struct Board {
-    blocks: [[Block; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize]
-}
-
-#[derive(Copy, Clone)]
-pub enum Block {
-    Free,
-    Occupied(Rgb),
-}
-
-impl Board {
-    fn new() -> Self {
-        let blocks = [[Block::Free; BOARD_WIDTH as usize]; BOARD_HEIGHT as usize];
-        Board { blocks }
+        self.bot_inputs.pop_front()
     }
 }
 
-struct SevenGenerator {
-    rng: ThreadRng,
-    types: [Type; 7],
-    idx: usize,
-}
-
-impl SevenGenerator {
-    fn new() -> Self {
-        use super::tetrimino::Type::*;
-
-        let mut rng = thread_rng();
-        let mut pieces = [I, O, T, S, Z, J, L];
-        pieces.shuffle(&mut rng);
-
-        SevenGenerator {
-            rng,
-            types: pieces,
-            idx: 0,
+// once either side has finished a sprint it's down to time, until then it's lines
+fn ghost_status(engine: &Engine, ghost: &Ghost) -> String {
+    let finish = ghost.replay().finish;
+    if engine.mode() == Mode::Sprint {
+        let played = engine.played().as_millis() as i64;
+        match (engine.is_won(), finish) {
+            (true, Some(finish)) if finish.time as i64 >= played => {
+                return format!("ahead by {:.1} seconds", (finish.time as i64 - played) as f64 / 1000.0);
+            }
+            (true, Some(finish)) => return format!("behind by {:.1} seconds", (played - finish.time as i64) as f64 / 1000.0),
+            (true, None) => return "ahead, the ghost never finished".to_string(),
+            (false, Some(finish)) if ghost.engine().is_won() => {
+                return format!("behind, the ghost finished in {}", clock(Duration::from_millis(finish.time)));
+            }
+            _ => {}
         }
     }
-}
-
-impl Iterator for SevenGenerator {
-    type Item = Type;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx == 6 {
-            self.idx = 0;
-            self.types.shuffle(&mut self.rng);
-        }
-
-        let current = self.types[self.idx];
-        self.idx += 1;
-        Some(current)
+    let lines = engine.stats().lines as isize - ghost.engine().stats().lines as isize;
+    match lines {
+        0 => "level".to_string(),
+        1 => "ahead by 1 line".to_string(),
+        -1 => "behind by 1 line".to_string(),
+        lines if lines > 0 => format!("ahead by {} lines", lines),
+        lines => format!("behind by {} lines", -lines),
     }
-}
\ No newline at end of file
+}
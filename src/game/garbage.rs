@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Clear, Spin, BOARD_WIDTH};
+use crate::game::random::Rng;
+
+// lines sent for each combo count, combos past the end send the last entry
+const COMBO_ATTACK: [usize; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+const BACK_TO_BACK_ATTACK: usize = 1;
+const PERFECT_CLEAR_ATTACK: usize = 10;
+
+// how likely the hole is to move from one messy row to the next
+const MESSY_CHANCE: usize = 30;
+
+/// Lines of garbage a clear sends to the other player, before it cancels anything incoming.
+pub fn attack(clear: &Clear) -> usize {
+    if clear.lines == 0 {
+        return 0;
+    }
+
+    let lines = match (clear.spin, clear.lines) {
+        (Spin::Full, lines) => lines * 2,
+        (_, 4) => 4,
+        (_, lines) => lines - 1,
+    };
+
+    let back_to_back = if clear.back_to_back { BACK_TO_BACK_ATTACK } else { 0 };
+    let combo = COMBO_ATTACK[(clear.combo.max(0) as usize).min(COMBO_ATTACK.len() - 1)];
+    let perfect_clear = if clear.perfect_clear { PERFECT_CLEAR_ATTACK } else { 0 };
+
+    lines + back_to_back + combo + perfect_clear
+}
+
+/// Where the holes go in the garbage rows which rise under the stack.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Holes {
+    /// Every row of an attack has its hole in the same column, so it can be dug with one well.
+    Clean,
+
+    /// The hole sometimes moves from one row to the next.
+    Messy,
+
+    /// The hole moves on every row.
+    Cheese,
+}
+
+/// Picks the hole for every garbage row, from its own seeded stream so both players of a
+/// versus game see the same holes.
+#[derive(Serialize, Deserialize)]
+pub struct HoleGenerator {
+    holes: Holes,
+    rng: Rng,
+}
+
+impl HoleGenerator {
+    pub fn new(holes: Holes, rng: Rng) -> Self {
+        HoleGenerator { holes, rng }
+    }
+
+    /// The holes for the rows of one attack, from the top row down.
+    pub fn holes(&mut self, lines: usize) -> Vec<usize> {
+        let width = BOARD_WIDTH as usize;
+        let mut holes = Vec::with_capacity(lines);
+        let mut hole = self.rng.below(width);
+
+        for i in 0..lines {
+            let moves = match self.holes {
+                Holes::Clean => false,
+                Holes::Messy => self.rng.below(100) < MESSY_CHANCE,
+                Holes::Cheese => true,
+            };
+            if i > 0 && moves {
+                // any column but the one the row above has
+                hole = (hole + 1 + self.rng.below(width - 1)) % width;
+            }
+            holes.push(hole);
+        }
+
+        holes
+    }
+}
+
+/// Garbage sent by the other player which hasn't risen yet, in the order the attacks arrived.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Queue {
+    attacks: VecDeque<usize>,
+}
+
+impl Queue {
+    pub fn push(&mut self, lines: usize) {
+        if lines > 0 {
+            self.attacks.push_back(lines);
+        }
+    }
+
+    /// Takes lines off the oldest attacks first, returns what's left of the attack once
+    /// there's nothing more to cancel.
+    pub fn cancel(&mut self, mut attack: usize) -> usize {
+        while attack > 0 {
+            let front = match self.attacks.front_mut() {
+                Some(front) => front,
+                None => break,
+            };
+
+            let cancelled = attack.min(*front);
+            *front -= cancelled;
+            attack -= cancelled;
+            if *front == 0 {
+                self.attacks.pop_front();
+            }
+        }
+
+        attack
+    }
+
+    pub fn lines(&self) -> usize {
+        self.attacks.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attacks.is_empty()
+    }
+
+    /// Every attack waiting, oldest first, leaving the queue empty.
+    pub fn take(&mut self) -> Vec<usize> {
+        self.attacks.drain(..).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.attacks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: usize, spin: Spin) -> Clear {
+        Clear { lines, spin, back_to_back: false, combo: 0, perfect_clear: false }
+    }
+
+    #[test]
+    fn clears_send_by_lines_and_spin() {
+        let sent: Vec<usize> = (0..=4).map(|lines| attack(&clear(lines, Spin::None))).collect();
+        assert_eq!(sent, [0, 0, 1, 2, 4]);
+
+        let sent: Vec<usize> = (0..=3).map(|lines| attack(&clear(lines, Spin::Full))).collect();
+        assert_eq!(sent, [0, 2, 4, 6]);
+
+        assert_eq!(attack(&clear(1, Spin::Mini)), 0);
+        assert_eq!(attack(&clear(2, Spin::Mini)), 1);
+    }
+
+    #[test]
+    fn bonuses_are_added_on() {
+        assert_eq!(attack(&Clear { back_to_back: true, ..clear(4, Spin::None) }), 5);
+        assert_eq!(attack(&Clear { back_to_back: true, ..clear(2, Spin::Full) }), 5);
+        assert_eq!(attack(&Clear { perfect_clear: true, ..clear(4, Spin::None) }), 14);
+
+        let sent: Vec<usize> = (0..12).map(|combo| attack(&Clear { combo, ..clear(1, Spin::None) })).collect();
+        assert_eq!(sent, [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5, 5]);
+
+        // a piece which clears nothing sends nothing, whatever came before it
+        assert_eq!(attack(&Clear { back_to_back: true, combo: 5, ..clear(0, Spin::Full) }), 0);
+    }
+
+    #[test]
+    fn attacks_cancel_the_oldest_garbage_first() {
+        let mut queue = Queue::default();
+        queue.push(2);
+        queue.push(0);
+        queue.push(3);
+        assert_eq!(queue.lines(), 5);
+
+        assert_eq!(queue.cancel(3), 0);
+        assert_eq!(queue.take(), [2]);
+        assert!(queue.is_empty());
+
+        queue.push(1);
+        assert_eq!(queue.cancel(4), 3);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn holes_move_by_the_kind_of_garbage() {
+        let clean = HoleGenerator::new(Holes::Clean, Rng::new(7)).holes(8);
+        assert!(clean.iter().all(|hole| *hole == clean[0]));
+
+        let cheese = HoleGenerator::new(Holes::Cheese, Rng::new(7)).holes(8);
+        assert!(cheese.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(cheese.iter().all(|hole| *hole < BOARD_WIDTH as usize));
+    }
+}
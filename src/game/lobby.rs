@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Mode, Rules};
+use crate::game::snapshot::Snapshot;
+use crate::game::view::{View, VIEW_HEIGHT, VIEW_WIDTH};
+use crate::net::Connection;
+use crate::relay::{ClientMessage, RoomState, ServerMessage, PROTOCOL_VERSION};
+
+// the room line goes under both views
+const STATUS_ROW: u16 = VIEW_HEIGHT + 1;
+
+/// A room on a `tetrust-server`, any number of players each play their own board and garbage
+/// goes round the others. The local board is shown with one of the others next to it.
+pub struct Lobby<R: Read, W: Write> {
+    stdin: Keys<R>,
+    stdout: W,
+    connection: Connection<ServerMessage>,
+    player: usize,
+    room: RoomState,
+    rules: Rules,
+
+    engine: Engine,
+    view: View,
+    last_snapshot: Option<Snapshot>,
+
+    // in a round and still standing
+    playing: bool,
+    ready: bool,
+
+    // the winner of the last round, if there's been one
+    result: Option<Option<usize>>,
+
+    // mirrors of everyone else's boards, one of them is shown at a time
+    opponents: BTreeMap<usize, Engine>,
+    opponent_view: View,
+    focus: Option<usize>,
+
+    failure: Option<String>,
+}
+
+impl<R: Read, W: Write> Lobby<R, W> {
+    /// Joins the room on the server, it's created with these rules if it doesn't exist yet.
+    pub fn new(r: R, w: W, mut connection: Connection<ServerMessage>, room: &str, name: &str, rules: Rules) -> io::Result<Lobby<R, RawTerminal<W>>> {
+        connection.send(&ClientMessage::Join { version: PROTOCOL_VERSION, room: room.to_string(), name: name.to_string(), rules })?;
+        let (player, room) = loop {
+            match connection.receive()? {
+                ServerMessage::Joined { player, room } => break (player, room),
+                ServerMessage::Error { message } => return Err(io::Error::other(message)),
+                _ => {}
+            }
+        };
+
+        let mut view = View::new(1, 1, Controls::single());
+        view.set_quit_key(Some("q"));
+        let mut opponent_view = View::new(2 + VIEW_WIDTH, 1, Controls::none());
+        opponent_view.set_quit_key(None);
+
+        Ok(Lobby {
+            stdin: r.keys(),
+            stdout: w.into_raw_mode()?,
+            connection,
+            player,
+            room,
+            rules,
+            engine: Engine::new(rules),
+            view,
+            last_snapshot: None,
+            playing: false,
+            ready: false,
+            result: None,
+            opponents: BTreeMap::new(),
+            opponent_view,
+            focus: None,
+            failure: None,
+        })
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        self.view.set_animations(animations);
+        self.opponent_view.set_animations(animations);
+    }
+
+    pub fn start(&mut self) -> io::Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
+
+        'main: loop {
+            thread::sleep(Duration::from_millis(50));
+
+            while let Some(Ok(key)) = self.stdin.next() {
+                match key {
+                    Key::Char('q') => break 'main,
+                    Key::Char('\t') => self.next_focus(),
+                    Key::Char('\n') if !self.playing && !self.ready && self.failure.is_none() => {
+                        self.ready = true;
+                        self.send(&ClientMessage::Ready);
+                    }
+                    _ if !self.playing => {}
+                    key => {
+                        if let Some(input) = self.view.controls().input(key) {
+                            self.engine.input(input);
+                        }
+                    }
+                }
+            }
+
+            self.receive();
+
+            if self.playing {
+                self.engine.tick(Duration::from_millis(50));
+
+                let attack = self.engine.take_attack();
+                if attack > 0 {
+                    self.send(&ClientMessage::Garbage { lines: attack });
+                }
+                self.send_board();
+
+                if self.engine.is_lost() {
+                    self.playing = false;
+                    self.send(&ClientMessage::GameOver);
+                }
+            }
+
+            if self.connection.needs_heartbeat() {
+                self.send(&ClientMessage::Ping);
+            }
+
+            self.update_banners();
+            self.draw()?;
+        }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+
+        Ok(())
+    }
+
+    fn receive(&mut self) {
+        while self.failure.is_none() {
+            let message = match self.connection.poll() {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(_) => {
+                    self.failure = Some("DISCONNECTED".to_string());
+                    self.playing = false;
+                    return;
+                }
+            };
+
+            match message {
+                ServerMessage::Room { room } => {
+                    self.ready = room.players.iter().any(|player| player.id == self.player && player.ready);
+                    self.opponents.retain(|id, _| room.players.iter().any(|player| player.id == *id));
+                    self.room = room;
+                }
+                ServerMessage::Start { seed, rules } => self.new_round(seed, rules),
+                ServerMessage::Garbage { lines, .. } if self.playing => self.engine.receive_garbage(lines),
+                ServerMessage::Board { player, snapshot } => {
+                    let rules = self.rules;
+                    let opponent = self.opponents.entry(player).or_insert_with(|| Engine::new(rules));
+                    if opponent.mirror(&snapshot).is_err() {
+                        self.failure = Some("DESYNC".to_string());
+                    }
+                }
+                ServerMessage::RoundOver { winner } => {
+                    self.playing = false;
+                    self.result = Some(winner);
+                }
+                ServerMessage::Error { message } => self.failure = Some(message),
+                _ => {}
+            }
+        }
+    }
+
+    fn new_round(&mut self, seed: u64, rules: Rules) {
+        self.rules = rules;
+        self.engine = Engine::with_seed(rules, Mode::Marathon, seed);
+        self.view.reset();
+        self.opponent_view.reset();
+        self.last_snapshot = None;
+        self.playing = true;
+        self.ready = false;
+        self.result = None;
+
+        // everyone starts from the same board, until their own snapshots come in
+        self.opponents = self.room.players.iter()
+            .filter(|player| player.id != self.player)
+            .map(|player| (player.id, Engine::with_seed(rules, Mode::Marathon, seed)))
+            .collect();
+    }
+
+    fn send_board(&mut self) {
+        let snapshot = self.engine.snapshot();
+        if self.last_snapshot.as_ref() == Some(&snapshot) {
+            return;
+        }
+
+        self.send(&ClientMessage::Board { snapshot: snapshot.clone() });
+        self.last_snapshot = Some(snapshot);
+    }
+
+    fn send(&mut self, message: &ClientMessage) {
+        if self.failure.is_none() && self.connection.send(message).is_err() {
+            self.failure = Some("DISCONNECTED".to_string());
+            self.playing = false;
+        }
+    }
+
+    // the opponent shown goes round everyone else in the room
+    fn next_focus(&mut self) {
+        let others: Vec<usize> = self.opponents.keys().copied().collect();
+        self.focus = match self.focus.and_then(|focus| others.iter().position(|id| *id == focus)) {
+            Some(i) => others.get(i + 1).or(others.first()).copied(),
+            None => others.first().copied(),
+        };
+        self.opponent_view.reset();
+    }
+
+    fn name(&self, id: usize) -> String {
+        self.room.players.iter()
+            .find(|player| player.id == id)
+            .map(|player| player.name.clone())
+            .unwrap_or_else(|| "someone".to_string())
+    }
+
+    fn update_banners(&mut self) {
+        if let Some(failure) = &self.failure {
+            self.view.set_banner(vec![failure.clone(), "q: quit".to_string()]);
+            return;
+        }
+
+        let ready = if self.ready { "waiting" } else { "enter: ready" }.to_string();
+        let banner = match self.result {
+            _ if self.playing => Vec::new(),
+            Some(Some(winner)) if winner == self.player => vec!["WINNER".to_string(), ready],
+            Some(Some(winner)) => vec![format!("{} WINS", self.name(winner)), ready],
+            Some(None) => vec!["DRAW".to_string(), ready],
+            None if self.room.playing && self.engine.is_lost() => vec!["TOP OUT".to_string()],
+            None if self.room.playing => vec!["WATCHING".to_string()],
+            None => vec![format!("ROOM {}", self.room.name), ready],
+        };
+        self.view.set_banner(banner);
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        self.view.draw(&mut self.stdout, &self.engine)?;
+
+        let opponents = &self.opponents;
+        let focus = self.focus.filter(|focus| opponents.contains_key(focus))
+            .or_else(|| opponents.keys().next().copied());
+        match focus.and_then(|focus| opponents.get(&focus)) {
+            Some(opponent) => self.opponent_view.draw(&mut self.stdout, opponent)?,
+            None => {
+                self.opponent_view.set_banner(vec!["WAITING FOR".to_string(), "PLAYERS".to_string()]);
+                self.opponent_view.draw(&mut self.stdout, &Engine::with_seed(self.rules, Mode::Marathon, 0))?;
+                self.opponent_view.reset();
+            }
+        }
+
+        // everyone in the room with their wins, the board being shown is marked
+        let players: Vec<String> = self.room.players.iter()
+            .map(|player| {
+                let shown = if Some(player.id) == focus { ">" } else { "" };
+                let state = match (self.room.playing, player.alive, player.ready) {
+                    (true, false, _) => " out",
+                    (false, _, true) => " ready",
+                    _ => "",
+                };
+                format!("{}{} {}{}", shown, player.name, player.wins, state)
+            })
+            .collect();
+        write!(
+            self.stdout, "{}{}{}room {}: {}   tab: next board",
+            cursor::Goto(1, STATUS_ROW), style::Reset, clear::CurrentLine, self.room.name, players.join(", "),
+        )?;
+        self.stdout.flush()
+    }
+}
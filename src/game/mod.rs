@@ -1,4 +1,30 @@
+#[allow(clippy::module_inception)]
 mod game;
+pub mod controls;
+pub mod coop;
+pub mod daily;
+pub mod dig;
+pub mod engine;
+pub mod finesse;
+pub mod fumen;
+pub mod garbage;
+pub mod lobby;
+pub mod movegen;
+pub mod online;
+pub mod puzzle;
+pub mod random;
+pub mod replay;
+pub mod save;
+pub mod snapshot;
+pub mod solver;
 pub mod tetrimino;
+pub mod versus;
+pub mod view;
+pub mod watch;
 
-pub use game::Game;
\ No newline at end of file
+pub use coop::Coop;
+pub use game::Game;
+pub use lobby::Lobby;
+pub use online::Online;
+pub use versus::Versus;
+pub use watch::Watch;
@@ -0,0 +1,324 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Board, Engine, Input, Rules, BOARD_HEIGHT};
+use crate::game::tetrimino::{Tetrimino, Type};
+
+// everything that moves a piece without locking it, in the order they're tried. Soft drops
+// stop the piece partway down so it can be shifted or rotated from there, they're tried last
+// so a path only takes them when nothing else is as short
+const MOVES: [Input; 6] = [
+    Input::Left,
+    Input::Right,
+    Input::RotateClockwise,
+    Input::RotateCounterClockwise,
+    Input::SonicDrop,
+    Input::SoftDrop,
+];
+
+// all that's worth trying in open air, see `in_open_air`
+const DROPS: [Input; 2] = [Input::SonicDrop, Input::SoftDrop];
+
+/// Where a piece ends up once it's locked, along with the shortest inputs which get it there.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Placement {
+    pub hold: bool,
+    pub tetrimino: Tetrimino,
+    pub x: isize,
+    pub y: isize,
+
+    /// Always ends with a hard drop, starts with a hold if this placement is for the held piece.
+    pub inputs: Vec<Input>,
+}
+
+impl Placement {
+    /// The board after this placement is locked and its lines are cleared, with the number of lines.
+    pub fn apply(&self, board: &Board) -> (Board, usize) {
+        let mut board = board.clone();
+        board.place(&self.tetrimino, self.x, self.y);
+        let rows = board.full_rows();
+        board.collapse(&rows);
+        (board, rows.len())
+    }
+
+    /// The board cells this placement takes up, sorted so placements can be compared.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = cells_of(&self.tetrimino, self.x, self.y);
+        cells.sort_unstable();
+        cells
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct Position {
+    x: isize,
+    y: isize,
+    state: usize,
+}
+
+/// Every distinct placement the piece can lock into from where it is, searching through
+/// shifts, rotations with kicks and soft drops so tucks and spins are found too. Without a
+/// lock delay a piece locks as soon as it lands, so it's only ever moved in the air.
+pub fn placements(board: &Board, tetrimino: Tetrimino, x: isize, y: isize, rules: Rules) -> Vec<Placement> {
+    let ttype = tetrimino.ttype;
+    let start = Position { x, y, state: tetrimino.state };
+    if !board.can_fit(x, y, tetrimino.block()) {
+        return Vec::new();
+    }
+
+    // breadth first, so the first path to any position is one of the shortest, and each
+    // position (x, y and rotation) is only searched from once however it was reached
+    let mut parents: HashMap<Position, Option<(Position, Input)>> = HashMap::with_capacity(1024);
+    let mut queue = VecDeque::new();
+    parents.insert(start, None);
+    queue.push_back(start);
+
+    let mut placements: Vec<Placement> = Vec::new();
+    let mut seen: Vec<Vec<(usize, usize)>> = Vec::new();
+    let mut landed = HashSet::new();
+    let surface = surface(board);
+    let locks_on_landing = rules.lock_delay.is_zero();
+
+    while let Some(position) = queue.pop_front() {
+        let moves: &[Input] = if in_open_air(surface, position) { &DROPS } else { &MOVES };
+        for &input in moves {
+            if let Some(next) = apply(board, ttype, position, input) {
+                // the engine would lock it right there, the hard drop from above covers that spot
+                if locks_on_landing && drop(board, ttype, next) == next {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = parents.entry(next) {
+                    entry.insert(Some((position, input)));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        // a soft drop lands where the position above it did, and that's been looked at already
+        if let Some(Some((_, Input::SoftDrop))) = parents.get(&position) {
+            continue;
+        }
+
+        // a hard drop from here is one more input, positions come out of the queue in order
+        // of how many inputs they took so the first hard drop onto some cells is the shortest
+        let dropped = drop(board, ttype, position);
+        if !landed.insert(dropped) {
+            continue;
+        }
+        let placement = Placement {
+            hold: false,
+            tetrimino: Tetrimino { ttype, state: dropped.state },
+            x: dropped.x,
+            y: dropped.y,
+            inputs: path(&parents, position, Input::HardDrop),
+        };
+
+        // pieces like S, Z and I can take up the same cells from two different states
+        let cells = placement.cells();
+        if !seen.contains(&cells) {
+            seen.push(cells);
+            placements.push(placement);
+        }
+    }
+
+    placements
+}
+
+/// Placements of a piece of the given type coming out of the spawn position.
+pub fn spawn_placements(board: &Board, ttype: Type, rules: Rules) -> Vec<Placement> {
+    let spawn = Engine::initialize_tetrimino(ttype);
+    placements(board, spawn.tetrimino, spawn.x, spawn.y, rules)
+}
+
+/// Placements for the piece which would come out of hold, the inputs start with a hold.
+pub fn hold_placements(engine: &Engine) -> Vec<Placement> {
+    if !engine.can_hold() {
+        return Vec::new();
+    }
+
+    let ttype = match engine.held_type().or_else(|| engine.next_type()) {
+        Some(ttype) => ttype,
+        None => return Vec::new(),
+    };
+    let mut placements = spawn_placements(engine.board(), ttype, *engine.rules());
+    for placement in placements.iter_mut() {
+        placement.hold = true;
+        placement.inputs.insert(0, Input::Hold);
+    }
+
+    placements
+}
+
+/// Placements of the engine's falling piece followed by the ones for the held piece.
+pub fn engine_placements(engine: &Engine) -> Vec<Placement> {
+    let mut placements = match engine.active() {
+        Some(active) => placements(engine.board(), active.tetrimino, active.x, active.y, *engine.rules()),
+        None => return Vec::new(),
+    };
+
+    placements.extend(hold_placements(engine));
+    placements
+}
+
+/// The shortest inputs which put a piece at the spawn position into the target placement.
+pub fn find_path(board: &Board, target: &Placement, rules: Rules) -> Option<Vec<Input>> {
+    let cells = target.cells();
+    spawn_placements(board, target.tetrimino.ttype, rules)
+        .into_iter()
+        .find(|placement| placement.cells() == cells)
+        .map(|placement| placement.inputs)
+}
+
+pub fn cells_of(tetrimino: &Tetrimino, x: isize, y: isize) -> Vec<(usize, usize)> {
+    let mut cells = Vec::with_capacity(4);
+    for (yi, row) in tetrimino.block().iter().enumerate() {
+        for (xi, col) in row.iter().enumerate() {
+            if *col == 1 {
+                cells.push(((x + xi as isize) as usize, (y + yi as isize) as usize));
+            }
+        }
+    }
+    cells
+}
+
+// the same rules the engine moves its pieces by
+fn apply(board: &Board, ttype: Type, position: Position, input: Input) -> Option<Position> {
+    let tetrimino = Tetrimino { ttype, state: position.state };
+
+    let next = match input {
+        Input::Left => Position { x: position.x - 1, ..position },
+        Input::Right => Position { x: position.x + 1, ..position },
+        Input::SoftDrop => Position { y: position.y + 1, ..position },
+        Input::SonicDrop => drop(board, ttype, position),
+        Input::RotateClockwise | Input::RotateCounterClockwise => {
+            let clockwise = input == Input::RotateClockwise;
+            let (rotated, x, y, _) = board.try_rotate(&tetrimino, position.x, position.y, clockwise)?;
+            return Some(Position { x, y, state: rotated.state });
+        }
+        _ => return None,
+    };
+
+    if next != position && board.can_fit(next.x, next.y, tetrimino.block()) {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+// shifts and rotations, kicks and all, do the same at any height where the rows around the piece
+// and the row above are empty, so once the piece is that far down in open air there's nothing
+// new to find by moving it, only by dropping it on to where the stack is
+fn in_open_air(surface: isize, position: Position) -> bool {
+    position.y - 3 >= 0 && position.y + 5 < surface
+}
+
+// the highest row with anything in it, or the floor
+fn surface(board: &Board) -> isize {
+    (0..BOARD_HEIGHT as usize)
+        .find(|&y| (0..board.width()).any(|x| board.is_occupied(x, y)))
+        .unwrap_or(BOARD_HEIGHT as usize) as isize
+}
+
+fn drop(board: &Board, ttype: Type, position: Position) -> Position {
+    let block = Tetrimino { ttype, state: position.state };
+    let mut y = position.y;
+    while board.can_fit(position.x, y + 1, block.block()) {
+        y += 1;
+    }
+    Position { y, ..position }
+}
+
+fn path(parents: &HashMap<Position, Option<(Position, Input)>>, mut position: Position, last: Input) -> Vec<Input> {
+    let mut inputs = vec![last];
+    while let Some(Some((parent, input))) = parents.get(&position) {
+        inputs.push(*input);
+        position = *parent;
+    }
+    inputs.reverse();
+    inputs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::game::puzzle::{Goal, Puzzle};
+
+    const TUNNEL: [&str; 6] = ["XXXXXX....", "..........", "..........", "XXXXXX....", "..........", ".........."];
+
+    // plays each placement's inputs in a fresh game and checks the piece locked where it said
+    fn check_paths(rows: &[&str], ttype: Type, rules: Rules) -> usize {
+        let board = Board::from_rows(rows).unwrap();
+        let puzzle = Puzzle {
+            name: "paths".to_string(),
+            board: rows.iter().map(|row| row.to_string()).collect(),
+            queue: format!("{}{}", ttype.letter(), ttype.letter()),
+            hold: None,
+            goal: Goal::PerfectClear,
+        };
+
+        let placements = spawn_placements(&board, ttype, rules);
+        for placement in &placements {
+            let mut engine = Engine::with_puzzle(rules, &puzzle).unwrap();
+            for &input in &placement.inputs {
+                engine.input(input);
+            }
+            assert_eq!(engine.board().rows(), placement.apply(&board).0.rows(), "{:?}", placement.inputs);
+        }
+        placements.len()
+    }
+
+    #[test]
+    fn every_column_and_rotation_is_found_on_an_empty_board() {
+        let counts: Vec<(Type, usize)> = Type::ALL.iter()
+            .map(|&ttype| (ttype, spawn_placements(&Board::new(), ttype, Rules::default()).len()))
+            .collect();
+
+        // S, Z and I cover the same cells from opposite states, O only has the one
+        for (ttype, count) in counts {
+            let expected = match ttype {
+                Type::O => 9,
+                Type::I | Type::S | Type::Z => 17,
+                Type::T | Type::L | Type::J => 34,
+            };
+            assert_eq!(count, expected, "{:?}", ttype);
+        }
+    }
+
+    // the O can't kick up into the tunnel, it has to be stopped level with it
+    #[test]
+    fn a_piece_is_slid_into_a_tunnel_partway_down() {
+        let board = Board::from_rows(&TUNNEL).unwrap();
+        let top = BOARD_HEIGHT as usize - 5;
+        let tunnel = spawn_placements(&board, Type::O, Rules::default()).into_iter()
+            .find(|placement| placement.cells() == [(0, top), (0, top + 1), (1, top), (1, top + 1)])
+            .expect("the far end of the tunnel should be reachable");
+
+        assert!(tunnel.inputs.contains(&Input::SoftDrop));
+        assert_eq!(tunnel.apply(&board).0.rows()[top], "OO........");
+    }
+
+    #[test]
+    fn every_path_ends_where_it_says() {
+        for ttype in Type::ALL {
+            check_paths(&TUNNEL, ttype, Rules::default());
+        }
+    }
+
+    // nothing can be moved once it's down, the O has to land on the tunnel's floor to get in
+    #[test]
+    fn without_a_lock_delay_pieces_are_only_moved_in_the_air() {
+        let rules = Rules { lock_delay: Duration::ZERO, ..Rules::default() };
+        for ttype in Type::ALL {
+            check_paths(&TUNNEL, ttype, rules);
+        }
+
+        let board = Board::from_rows(&TUNNEL).unwrap();
+        let top = BOARD_HEIGHT as usize - 5;
+        assert!(spawn_placements(&board, Type::O, rules).iter().all(|placement| placement.cells()[0] != (0, top)));
+        assert_eq!(spawn_placements(&Board::new(), Type::T, rules).len(), 34);
+    }
+}
@@ -0,0 +1,358 @@
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Mode, Rules};
+use crate::game::snapshot::Snapshot;
+use crate::game::view::{View, VIEW_WIDTH};
+use crate::net::Connection;
+
+/// Bumped whenever the messages change, both sides have to agree on it.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// What the two sides of an online game tell each other, one per line.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Hello { version: u32 },
+    /// From the host, a round starts with both sides playing the same pieces.
+    Start { seed: u64, rules: Rules },
+    /// With all the garbage sent this round so far, so the other side can tell if any went missing.
+    Garbage { lines: usize, total: usize },
+    /// The sender's board, with how much garbage it has been sent this round so the other side
+    /// can tell when they've drifted apart.
+    Board { seed: u64, garbage_received: usize, snapshot: Snapshot },
+    GameOver,
+    Rematch,
+    Ping,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+/// Versus against someone on another terminal. Only the local board is played here, the other
+/// one is a mirror of the snapshots the other side sends.
+pub struct Online<R: Read, W: Write> {
+    stdin: Keys<R>,
+    stdout: W,
+    connection: Connection<Message>,
+    role: Role,
+    rules: Rules,
+
+    // the round being played and the one before, boards from the last round can still be on
+    // their way when the next one starts
+    seed: u64,
+    last_seed: Option<u64>,
+
+    engine: Engine,
+    view: View,
+    opponent: Engine,
+    opponent_view: View,
+    last_snapshot: Option<Snapshot>,
+
+    // garbage sent and received this round
+    sent: usize,
+    received: usize,
+
+    wins: usize,
+    losses: usize,
+    round_over: bool,
+    rematch: (bool, bool),
+
+    // set when the game can't go on, disconnected or out of sync
+    failure: Option<&'static str>,
+}
+
+impl<R: Read, W: Write> Online<R, W> {
+    /// Agrees on a version and the first round with the other side, the host's rules are the
+    /// ones played.
+    pub fn new(r: R, w: W, mut connection: Connection<Message>, role: Role, rules: Rules) -> io::Result<Online<R, RawTerminal<W>>> {
+        let (seed, rules) = handshake(&mut connection, role, rules)?;
+
+        let mut view = View::new(1, 1, Controls::single());
+        view.set_quit_key(Some("q"));
+        let mut opponent_view = View::new(2 + VIEW_WIDTH, 1, Controls::none());
+        opponent_view.set_quit_key(None);
+
+        let mut online = Online {
+            stdin: r.keys(),
+            stdout: w.into_raw_mode()?,
+            connection,
+            role,
+            rules,
+            seed,
+            last_seed: None,
+            engine: Engine::new(rules),
+            view,
+            opponent: Engine::new(rules),
+            opponent_view,
+            last_snapshot: None,
+            sent: 0,
+            received: 0,
+            wins: 0,
+            losses: 0,
+            round_over: false,
+            rematch: (false, false),
+            failure: None,
+        };
+        online.new_round(seed, rules);
+        Ok(online)
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        self.view.set_animations(animations);
+        self.opponent_view.set_animations(animations);
+    }
+
+    pub fn start(&mut self) -> io::Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
+
+        'main: loop {
+            thread::sleep(Duration::from_millis(50));
+
+            while let Some(Ok(key)) = self.stdin.next() {
+                match key {
+                    Key::Char('q') => break 'main,
+                    Key::Char('\n') if self.round_over && self.failure.is_none() => {
+                        self.rematch.0 = true;
+                        self.send(&Message::Rematch);
+                        self.try_rematch();
+                    }
+                    _ if self.round_over => {}
+                    key => {
+                        if let Some(input) = self.view.controls().input(key) {
+                            self.engine.input(input);
+                        }
+                    }
+                }
+            }
+
+            self.receive();
+
+            if !self.round_over {
+                self.engine.tick(Duration::from_millis(50));
+
+                let attack = self.engine.take_attack();
+                if attack > 0 {
+                    self.sent += attack;
+                    self.send(&Message::Garbage { lines: attack, total: self.sent });
+                }
+
+                self.send_board();
+
+                if self.engine.is_lost() {
+                    self.send(&Message::GameOver);
+                    self.end_round(false);
+                }
+            }
+
+            if self.connection.needs_heartbeat() {
+                self.send(&Message::Ping);
+            }
+
+            self.update_banners();
+            self.view.draw(&mut self.stdout, &self.engine)?;
+            self.opponent_view.draw(&mut self.stdout, &self.opponent)?;
+            self.stdout.flush()?;
+        }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+
+        Ok(())
+    }
+
+    fn new_round(&mut self, seed: u64, rules: Rules) {
+        self.last_seed = Some(self.seed).filter(|last| *last != seed);
+        self.seed = seed;
+        self.rules = rules;
+        self.engine = Engine::with_seed(rules, Mode::Marathon, seed);
+        self.opponent = Engine::with_seed(rules, Mode::Marathon, seed);
+        self.view.reset();
+        self.opponent_view.reset();
+        self.last_snapshot = None;
+        self.sent = 0;
+        self.received = 0;
+        self.round_over = false;
+        self.rematch = (false, false);
+    }
+
+    // the host picks the seed for every round, once both sides have asked for another one
+    fn try_rematch(&mut self) {
+        if self.role == Role::Host && self.rematch == (true, true) {
+            let seed = rand::random();
+            self.send(&Message::Start { seed, rules: self.rules });
+            self.new_round(seed, self.rules);
+        }
+    }
+
+    fn end_round(&mut self, won: bool) {
+        if won {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+        self.round_over = true;
+    }
+
+    fn send_board(&mut self) {
+        let snapshot = self.engine.snapshot();
+        if self.last_snapshot.as_ref() == Some(&snapshot) {
+            return;
+        }
+
+        self.send(&Message::Board { seed: self.seed, garbage_received: self.received, snapshot: snapshot.clone() });
+        self.last_snapshot = Some(snapshot);
+    }
+
+    fn send(&mut self, message: &Message) {
+        if self.failure.is_none() && self.connection.send(message).is_err() {
+            self.fail("DISCONNECTED");
+        }
+    }
+
+    fn receive(&mut self) {
+        while self.failure.is_none() {
+            let message = match self.connection.poll() {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(_) => return self.fail("DISCONNECTED"),
+            };
+
+            match message {
+                Message::Start { seed, rules } if self.role == Role::Guest => self.new_round(seed, rules),
+                // the connection keeps messages in order, so every attack adds up to the total sent
+                Message::Garbage { lines, total } if !self.round_over => {
+                    self.received += lines;
+                    if self.received != total {
+                        return self.fail("DESYNC");
+                    }
+                    self.engine.receive_garbage(lines);
+                }
+                Message::Board { seed, .. } if Some(seed) == self.last_seed => {}
+                Message::Board { seed, garbage_received, snapshot } => self.mirror(seed, garbage_received, &snapshot),
+                Message::GameOver if !self.round_over => self.end_round(true),
+                Message::Rematch => {
+                    self.rematch.1 = true;
+                    self.try_rematch();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn mirror(&mut self, seed: u64, garbage_received: usize, snapshot: &Snapshot) {
+        // garbage sent from here can still be on its way, but the other side can never have
+        // been sent more than that
+        if seed != self.seed || garbage_received > self.sent || self.opponent.mirror(snapshot).is_err() {
+            self.fail("DESYNC");
+        }
+    }
+
+    fn fail(&mut self, reason: &'static str) {
+        self.failure = Some(reason);
+        self.round_over = true;
+    }
+
+    fn update_banners(&mut self) {
+        if let Some(reason) = self.failure {
+            let banner = vec![reason.to_string(), "q: quit".to_string()];
+            self.view.set_banner(banner.clone());
+            self.opponent_view.set_banner(banner);
+            return;
+        }
+        if !self.round_over {
+            return;
+        }
+
+        let lost = self.engine.is_lost();
+        let score = format!("{} - {}", self.wins, self.losses);
+        let rematch = |asked: bool| if asked { "waiting" } else { "enter: rematch" }.to_string();
+        let result = |lost: bool| if lost { "TOP OUT" } else { "WINNER" }.to_string();
+
+        self.view.set_banner(vec![result(lost), score.clone(), rematch(self.rematch.0)]);
+        self.opponent_view.set_banner(vec![result(!lost), score, rematch(self.rematch.1)]);
+    }
+}
+
+/// Agrees on a version with the other side, then the host starts the first round with its seed
+/// and rules and the guest finds out what they are.
+pub fn handshake(connection: &mut Connection<Message>, role: Role, rules: Rules) -> io::Result<(u64, Rules)> {
+    connection.send(&Message::Hello { version: PROTOCOL_VERSION })?;
+    match connection.receive()? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {}
+        Message::Hello { version } => return Err(invalid(format!("the other side speaks version {}, this is {}", version, PROTOCOL_VERSION))),
+        _ => return Err(invalid("expected a hello".to_string())),
+    }
+
+    match role {
+        Role::Host => {
+            let seed = rand::random();
+            connection.send(&Message::Start { seed, rules })?;
+            Ok((seed, rules))
+        }
+        Role::Guest => match connection.receive()? {
+            Message::Start { seed, rules } => Ok((seed, rules)),
+            _ => Err(invalid("expected the host to start".to_string())),
+        },
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{SocketAddr, TcpListener};
+
+    // both ends of a connection over loopback, the host's first
+    fn connect() -> (Connection<Message>, Connection<Message>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || Connection::join(addr).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+        (Connection::new(stream).unwrap(), guest.join().unwrap())
+    }
+
+    #[test]
+    fn the_guest_plays_the_hosts_seed_and_rules() {
+        let (mut host, mut guest) = connect();
+        let rules = Rules { lock_delay: Duration::from_millis(250), hold: false, ..Rules::default() };
+        let guest = thread::spawn(move || handshake(&mut guest, Role::Guest, Rules::default()).unwrap());
+
+        let (seed, _) = handshake(&mut host, Role::Host, rules).unwrap();
+        let (guest_seed, guest_rules) = guest.join().unwrap();
+        assert_eq!(guest_seed, seed);
+        assert_eq!(guest_rules.lock_delay, rules.lock_delay);
+        assert!(!guest_rules.hold);
+    }
+
+    #[test]
+    fn another_version_is_turned_away() {
+        let (mut host, mut guest) = connect();
+        guest.send(&Message::Hello { version: PROTOCOL_VERSION + 1 }).unwrap();
+
+        let err = handshake(&mut host, Role::Host, Rules::default()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn hanging_up_is_noticed() {
+        let (host, mut guest) = connect();
+        drop(host);
+
+        let err = guest.receive().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(guest.poll().is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Board, Clear, Spin};
+use crate::game::tetrimino::Type;
+use crate::storage;
+
+const PROGRESS_FILE: &str = "puzzles.json";
+
+/// A challenge, a starting board and a fixed queue of pieces with a goal to reach before they run out.
+///
+/// The board is given as rows down to the bottom of the board, `.` is empty, piece letters are
+/// blocks of that piece's colour and anything else is garbage.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+
+    #[serde(default)]
+    pub board: Vec<String>,
+    pub queue: String,
+
+    #[serde(default)]
+    pub hold: Option<char>,
+    pub goal: Goal,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Goal {
+    /// Clear at least this many lines in total.
+    Lines(usize),
+    Tetris,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+    PerfectClear,
+}
+
+impl Goal {
+    /// Whether the goal has been reached with this clear, lines is the total cleared so far.
+    pub fn is_met(self, clear: &Clear, lines: usize) -> bool {
+        match self {
+            Goal::Lines(target) => lines >= target,
+            Goal::Tetris => clear.lines == 4,
+            Goal::TSpinSingle => clear.spin == Spin::Full && clear.lines == 1,
+            Goal::TSpinDouble => clear.spin == Spin::Full && clear.lines == 2,
+            Goal::TSpinTriple => clear.spin == Spin::Full && clear.lines == 3,
+            Goal::PerfectClear => clear.perfect_clear,
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            Goal::Lines(1) => "clear a line".to_string(),
+            Goal::Lines(target) => format!("clear {} lines", target),
+            Goal::Tetris => "tetris".to_string(),
+            Goal::TSpinSingle => "t-spin single".to_string(),
+            Goal::TSpinDouble => "t-spin double".to_string(),
+            Goal::TSpinTriple => "t-spin triple".to_string(),
+            Goal::PerfectClear => "perfect clear".to_string(),
+        }
+    }
+}
+
+impl Puzzle {
+    pub fn board(&self) -> Result<Board, String> {
+        Board::from_rows(&self.board).map_err(|err| format!("puzzle {}: {}", self.name, err))
+    }
+
+    pub fn queue(&self) -> Result<Vec<Type>, String> {
+        self.queue.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| Type::from_letter(c.to_ascii_uppercase()).ok_or_else(|| format!("puzzle {} has an unknown piece {}", self.name, c)))
+            .collect()
+    }
+
+    pub fn hold(&self) -> Result<Option<Type>, String> {
+        match self.hold {
+            Some(c) => Type::from_letter(c.to_ascii_uppercase())
+                .map(Some)
+                .ok_or_else(|| format!("puzzle {} has an unknown held piece {}", self.name, c)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Puzzles which are played one after the other.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+// a file can hold a whole pack or a single puzzle
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PuzzleFile {
+    Pack(Pack),
+    Puzzle(Puzzle),
+}
+
+impl Pack {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Pack> {
+        let contents = fs::read_to_string(path)?;
+        match serde_json::from_str(&contents) {
+            Ok(PuzzleFile::Pack(pack)) => Ok(pack),
+            Ok(PuzzleFile::Puzzle(puzzle)) => Ok(Pack { name: puzzle.name.clone(), puzzles: vec![puzzle] }),
+            Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    }
+}
+
+/// How far into each pack has been solved, kept in the data directory.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Progress {
+    solved: BTreeMap<String, usize>,
+}
+
+impl Progress {
+    pub fn load() -> io::Result<Self> {
+        storage::load(PROGRESS_FILE)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::save(PROGRESS_FILE, self)
+    }
+
+    /// How many puzzles of the pack have been solved in order.
+    pub fn solved(&self, pack: &str) -> usize {
+        self.solved.get(pack).copied().unwrap_or(0)
+    }
+
+    pub fn record(&mut self, pack: &str, index: usize) {
+        let solved = self.solved.entry(pack.to_string()).or_insert(0);
+        *solved = (*solved).max(index + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::game::engine::{Engine, Input, Rules};
+
+    fn play(queue: &str, goal: Goal, inputs: &[Input]) -> Engine {
+        let puzzle = Puzzle {
+            name: "test".to_string(),
+            board: vec!["XXXXXXXX..".to_string(), "XXXXXXXX..".to_string()],
+            queue: queue.to_string(),
+            hold: None,
+            goal,
+        };
+        let mut engine = Engine::with_puzzle(Rules::default(), &puzzle).unwrap();
+        for &input in inputs {
+            engine.input(input);
+        }
+
+        // through the clear and entry delays
+        for _ in 0..3 {
+            engine.tick(Duration::from_secs(1));
+        }
+        engine
+    }
+
+    #[test]
+    fn reaching_the_goal_wins() {
+        let engine = play("OO", Goal::Lines(2), &[Input::Right, Input::Right, Input::Right, Input::Right, Input::HardDrop]);
+        assert!(engine.is_won());
+    }
+
+    #[test]
+    fn running_out_of_pieces_loses() {
+        let engine = play("OT", Goal::Tetris, &[Input::Right, Input::Right, Input::Right, Input::Right, Input::HardDrop]);
+        assert!(!engine.is_over());
+
+        let engine = play("O", Goal::Tetris, &[Input::Right, Input::Right, Input::Right, Input::Right, Input::HardDrop]);
+        assert!(engine.is_lost());
+    }
+
+    #[test]
+    fn a_file_is_a_pack_or_a_single_puzzle() {
+        let puzzle = r#"{"name":"one","queue":"tio","hold":"l","goal":{"lines":2}}"#;
+        let Ok(PuzzleFile::Puzzle(puzzle)) = serde_json::from_str(puzzle) else { panic!("not a puzzle") };
+        assert_eq!(puzzle.queue(), Ok(vec![Type::T, Type::I, Type::O]));
+        assert_eq!(puzzle.hold(), Ok(Some(Type::L)));
+        assert_eq!(puzzle.goal, Goal::Lines(2));
+        assert!(puzzle.board().unwrap().rows().iter().all(|row| row == ".........."));
+
+        let pack = r#"{"name":"pack","puzzles":[{"name":"pc","board":["XXXXXXXX.."],"queue":"O","goal":"perfect_clear"}]}"#;
+        let Ok(PuzzleFile::Pack(pack)) = serde_json::from_str(pack) else { panic!("not a pack") };
+        assert_eq!(pack.puzzles[0].goal, Goal::PerfectClear);
+    }
+
+    #[test]
+    fn unknown_pieces_are_errors() {
+        let puzzle = Puzzle { name: "bad".to_string(), board: Vec::new(), queue: "TQ".to_string(), hold: Some('x'), goal: Goal::Tetris };
+        assert_eq!(puzzle.queue(), Err("puzzle bad has an unknown piece Q".to_string()));
+        assert_eq!(puzzle.hold(), Err("puzzle bad has an unknown held piece x".to_string()));
+    }
+
+    #[test]
+    fn progress_only_moves_forward() {
+        let mut progress = Progress::default();
+        progress.record("pack", 2);
+        progress.record("pack", 0);
+        assert_eq!(progress.solved("pack"), 3);
+        assert_eq!(progress.solved("other"), 0);
+    }
+}
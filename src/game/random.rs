@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::tetrimino::Type;
+
+/// Small deterministic generator (splitmix64), the same seed deals the same pieces everywhere
+/// so seeds can be shared, replayed and simulated.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in 0..n, n must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Randomizer {
+    /// All seven pieces in a shuffled bag, then another bag.
+    SevenBag,
+
+    /// Every piece is picked on its own, so droughts and floods can happen.
+    Random,
+}
+
+/// Deals the pieces, this is the queue the next piece comes out of.
+#[derive(Serialize, Deserialize)]
+pub struct Generator {
+    randomizer: Randomizer,
+    rng: Rng,
+    bag: Vec<Type>,
+
+    // deals the bag once in order and then runs out, for puzzles
+    fixed: bool,
+}
+
+impl Generator {
+    pub fn new(randomizer: Randomizer, seed: u64) -> Self {
+        Generator {
+            randomizer,
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(Type::ALL.len()),
+            fixed: false,
+        }
+    }
+
+    /// Deals exactly these pieces in order and nothing after them.
+    pub fn fixed(mut queue: Vec<Type>) -> Self {
+        // pieces come off the end of the bag
+        queue.reverse();
+        Generator {
+            randomizer: Randomizer::SevenBag,
+            rng: Rng::new(0),
+            bag: queue,
+            fixed: true,
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Type;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fixed {
+            return self.bag.pop();
+        }
+
+        match self.randomizer {
+            Randomizer::SevenBag => {
+                if self.bag.is_empty() {
+                    self.bag.extend_from_slice(&Type::ALL);
+                    self.rng.shuffle(&mut self.bag);
+                }
+                self.bag.pop()
+            }
+            Randomizer::Random => Some(Type::ALL[self.rng.below(Type::ALL.len())]),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::{Engine, Input, Mode, Rules};
+use crate::storage;
+
+/// Everything needed to play a game again exactly as it went, the engine is deterministic
+/// given the seed, the rules and when each input happened.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub mode: Mode,
+    pub seed: u64,
+    pub rules: Rules,
+
+    /// How long each tick was in milliseconds, inputs only happen between ticks.
+    pub frame: u64,
+    pub inputs: Vec<Event>,
+
+    /// How the game ended, none when it was topped out or quit.
+    pub finish: Option<Finish>,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// Milliseconds of play before the input.
+    pub at: u64,
+    pub input: Input,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Finish {
+    /// Milliseconds of play.
+    pub time: u64,
+    pub lines: usize,
+    pub score: usize,
+    pub pieces: usize,
+}
+
+impl Replay {
+    /// Starts recording the engine, which hasn't been played yet.
+    pub fn new(engine: &Engine, frame: Duration) -> Self {
+        Replay {
+            mode: engine.mode(),
+            seed: engine.seed(),
+            rules: *engine.rules(),
+            frame: frame.as_millis() as u64,
+            inputs: Vec::new(),
+            finish: None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let contents = serde_json::to_string(self)?;
+        fs::write(path, contents)
+    }
+
+    /// The best finished game of the mode, kept in the data directory.
+    pub fn load_best(mode: Mode) -> io::Result<Option<Self>> {
+        storage::load(&best_file(mode))
+    }
+
+    /// Keeps the replay as the best of its mode if it beats the one kept, returns whether it did.
+    pub fn save_if_best(&self) -> io::Result<bool> {
+        let finish = match self.finish {
+            Some(finish) => finish,
+            None => return Ok(false),
+        };
+
+        let best = Self::load_best(self.mode)?.and_then(|best| best.finish);
+        let better = match (best, self.mode) {
+            (None, _) => true,
+            (Some(best), Mode::Ultra) => finish.score > best.score,
+            (Some(best), _) => finish.time < best.time,
+        };
+        if better {
+            storage::save(&best_file(self.mode), &Some(self))?;
+        }
+        Ok(better)
+    }
+
+    pub fn record(&mut self, engine: &Engine, input: Input) {
+        self.inputs.push(Event { at: engine.played().as_millis() as u64, input });
+    }
+
+    /// Notes how the game went, only finished games are kept as results.
+    pub fn finish(&mut self, engine: &Engine) {
+        if !engine.is_won() {
+            return;
+        }
+
+        let stats = engine.stats();
+        self.finish = Some(Finish {
+            time: engine.played().as_millis() as u64,
+            lines: stats.lines,
+            score: stats.score,
+            pieces: stats.pieces,
+        });
+    }
+}
+
+fn best_file(mode: Mode) -> String {
+    format!("best-{:?}.json", mode).to_lowercase()
+}
+
+/// A recorded game played back alongside a live one, kept at the same point in time.
+pub struct Ghost {
+    replay: Replay,
+    engine: Engine,
+    next: usize,
+}
+
+impl Ghost {
+    pub fn new(replay: Replay) -> Self {
+        let engine = Engine::with_seed(replay.rules, replay.mode, replay.seed);
+        Ghost { replay, engine, next: 0 }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Plays the replay on until it has been played for as long as `played`.
+    pub fn advance(&mut self, played: Duration) {
+        let frame = Duration::from_millis(self.replay.frame.max(1));
+        while self.engine.played() < played && !self.engine.is_over() {
+            let now = self.engine.played().as_millis() as u64;
+            while let Some(event) = self.replay.inputs.get(self.next).filter(|event| event.at <= now) {
+                self.engine.input(event.input);
+                self.next += 1;
+            }
+            self.engine.tick(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{Bot, Heuristic, Weights};
+
+    const FRAME: Duration = Duration::from_millis(50);
+
+    // a bot plays the game the way the game loop would, one tick after each piece's inputs
+    fn record(mode: Mode, seed: u64) -> (Replay, Engine) {
+        let mut engine = Engine::with_seed(Rules::default(), mode, seed);
+        let mut replay = Replay::new(&engine, FRAME);
+        let mut bot = Heuristic::new(Weights::default());
+
+        while !engine.is_over() {
+            if engine.active().is_some() {
+                let placement = bot.suggest(&engine).expect("the bot should have somewhere to go");
+                for input in placement.inputs {
+                    replay.record(&engine, input);
+                    engine.input(input);
+                }
+            }
+            engine.tick(FRAME);
+        }
+
+        replay.finish(&engine);
+        (replay, engine)
+    }
+
+    #[test]
+    fn a_replay_plays_out_the_same_game() {
+        let (replay, engine) = record(Mode::Sprint, 42);
+        let finish = replay.finish.expect("the sprint should have been finished");
+        assert_eq!(finish.lines, engine.stats().lines);
+
+        // through a file and back, the way replays are kept
+        let replay: Replay = serde_json::from_str(&serde_json::to_string(&replay).unwrap()).unwrap();
+        let mut ghost = Ghost::new(replay);
+        ghost.advance(Duration::from_millis(finish.time) + FRAME);
+
+        let played = ghost.engine();
+        assert!(played.is_won());
+        assert_eq!(played.played().as_millis() as u64, finish.time);
+        assert_eq!(played.stats().lines, finish.lines);
+        assert_eq!(played.stats().score, finish.score);
+        assert_eq!(played.stats().pieces, finish.pieces);
+        assert_eq!(played.board().rows(), engine.board().rows());
+    }
+
+    #[test]
+    fn unfinished_games_have_no_result() {
+        let engine = Engine::with_seed(Rules::default(), Mode::Sprint, 42);
+        let mut replay = Replay::new(&engine, FRAME);
+        replay.finish(&engine);
+        assert!(replay.finish.is_none());
+    }
+}
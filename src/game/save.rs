@@ -0,0 +1,103 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::Engine;
+use crate::game::replay::Replay;
+use crate::storage;
+
+const SAVE_FILE: &str = "save.json";
+
+/// A game which was quit partway through, kept in the data directory until it's carried on.
+/// The whole engine is kept, down to the randomizer and the timers, so it plays on exactly as
+/// it would have.
+#[derive(Serialize, Deserialize)]
+pub struct Save {
+    pub engine: Engine,
+
+    /// What had been recorded of the game, a resumed race records on into it.
+    pub replay: Option<Replay>,
+}
+
+// the same file, written without taking the game apart
+#[derive(Serialize)]
+struct SaveRef<'a> {
+    engine: &'a Engine,
+    replay: Option<&'a Replay>,
+}
+
+impl Save {
+    pub fn load() -> io::Result<Option<Self>> {
+        storage::load(SAVE_FILE)
+    }
+
+    /// Keeps the game to carry on with later, in place of any other saved game.
+    pub fn write(engine: &Engine, replay: Option<&Replay>) -> io::Result<()> {
+        storage::save(SAVE_FILE, &SaveRef { engine, replay })
+    }
+
+    /// There's only ever one saved game, it's gone once it has been resumed or turned down.
+    pub fn remove() -> io::Result<()> {
+        storage::remove(SAVE_FILE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::game::engine::{Board, Input, Rules, BOARD_HEIGHT, BOARD_WIDTH, COOP_BOARD_WIDTH};
+
+    fn resave(engine: &Engine) -> Engine {
+        let json = serde_json::to_string(&SaveRef { engine, replay: None }).unwrap();
+        serde_json::from_str::<Save>(&json).unwrap().engine
+    }
+
+    #[test]
+    fn a_coop_game_is_saved_with_its_wide_board() {
+        let mut engine = Engine::with_coop(Rules::default(), 5);
+        for _ in 0..4 {
+            engine.player_input(1, Input::Right);
+        }
+        engine.player_input(1, Input::HardDrop);
+        engine.tick(Duration::from_secs(1));
+
+        let saved = resave(&engine);
+        assert_eq!(saved.board().width(), COOP_BOARD_WIDTH as usize);
+        assert_eq!(saved.board().rows(), engine.board().rows());
+        assert!(saved.board().rows().last().unwrap()[BOARD_WIDTH as usize..].contains(|c| c != '.'));
+        assert_eq!(saved.players(), 2);
+    }
+
+    #[test]
+    fn a_resumed_game_plays_on_the_same() {
+        let mut engine = Engine::with_coop(Rules::default(), 9);
+        engine.player_input(0, Input::HardDrop);
+        let mut saved = resave(&engine);
+
+        for engine in [&mut engine, &mut saved] {
+            for _ in 0..10 {
+                engine.tick(Duration::from_millis(500));
+                engine.player_input(0, Input::HardDrop);
+                engine.player_input(1, Input::HardDrop);
+            }
+        }
+        assert_eq!(saved.board().rows(), engine.board().rows());
+        assert_eq!(saved.stats().pieces, engine.stats().pieces);
+        assert!(engine.stats().pieces > 10);
+    }
+
+    #[test]
+    fn a_board_has_to_be_whole_rows() {
+        let height = BOARD_HEIGHT as usize;
+        let board = |rows: Vec<String>| serde_json::from_value::<Board>(serde_json::json!(rows));
+        assert!(board(vec![".".repeat(20); height]).is_ok());
+        assert!(board(vec![".".repeat(21); height]).is_err());
+        assert!(board(vec![".".repeat(10); height - 1]).is_err());
+
+        let mut rows = vec![".".repeat(10); height];
+        rows[3] = ".".repeat(20);
+        assert!(board(rows).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::tetrimino::Type;
+
+/// What can be seen of an engine at one moment, enough to draw a copy of it somewhere else.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Rows from the top down, as `Board::rows` writes them.
+    pub board: Vec<String>,
+    pub active: Option<Piece>,
+    pub next: Option<Type>,
+    pub hold: Option<Type>,
+    pub score: usize,
+    pub lines: usize,
+    pub pieces: usize,
+    pub perfect_clears: usize,
+    pub callouts: Vec<String>,
+    pub perfect_clear: bool,
+    pub incoming: usize,
+    pub lost: bool,
+    pub won: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Piece {
+    #[serde(rename = "type")]
+    pub ttype: Type,
+    pub x: isize,
+    pub y: isize,
+    pub state: usize,
+}
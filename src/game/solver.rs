@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use crate::game::engine::{Board, Input, Rules, BOARD_HEIGHT, BOARD_WIDTH};
+use crate::game::movegen::{spawn_placements, Placement};
+use crate::game::tetrimino::Type;
+
+/// The most rows a perfect clear can be searched for in, the board fits in 64 bits up to here.
+pub const MAX_HEIGHT: usize = 6;
+
+/// Every sequence of placements which empties the board without going above `height` rows,
+/// using the queue in order with hold. The first piece of the queue is the one in play and
+/// placements out of hold start with a hold input, like the rest of the move generator.
+///
+/// There can be a lot of them with a long queue, the search stops once `limit` have been found.
+/// The inputs are for the default rules, with time to move a piece on the ground.
+pub fn perfect_clears(board: &Board, queue: &[Type], hold: Option<Type>, height: usize, limit: usize) -> Vec<Vec<Placement>> {
+    let height = height.min(MAX_HEIGHT);
+    let mut solver = Solver {
+        queue,
+        limit,
+        dead_ends: HashSet::new(),
+        path: Vec::new(),
+        solutions: Vec::new(),
+    };
+
+    if is_below(board, height) {
+        solver.search(board, 0, hold, height);
+    }
+
+    solver.solutions
+}
+
+struct Solver<'a> {
+    queue: &'a [Type],
+    limit: usize,
+
+    // states known not to lead to a perfect clear, the same board is often reached in different orders
+    dead_ends: HashSet<(u64, usize, usize, Option<Type>)>,
+    path: Vec<Placement>,
+    solutions: Vec<Vec<Placement>>,
+}
+
+impl<'a> Solver<'a> {
+    // returns whether any perfect clear was found from here
+    fn search(&mut self, board: &Board, next: usize, hold: Option<Type>, height: usize) -> bool {
+        let key = (occupancy(board, height), height, next, hold);
+        if self.dead_ends.contains(&key) || !can_fill(board, height, self.queue.len() - next + hold.iter().count()) {
+            return false;
+        }
+
+        let mut found = false;
+        for (placement, next, hold) in self.choices(board, next, hold, height) {
+            if self.solutions.len() >= self.limit {
+                return true;
+            }
+
+            let (after, lines) = placement.apply(board);
+            self.path.push(placement);
+
+            if lines == height {
+                self.solutions.push(self.path.clone());
+                found = true;
+            } else if self.search(&after, next, hold, height - lines) {
+                found = true;
+            }
+
+            self.path.pop();
+        }
+
+        if !found {
+            self.dead_ends.insert(key);
+        }
+        found
+    }
+
+    // placements of the piece in play and of the one out of hold, with what's left afterwards
+    fn choices(&self, board: &Board, next: usize, hold: Option<Type>, height: usize) -> Vec<(Placement, usize, Option<Type>)> {
+        let current = self.queue.get(next).copied();
+
+        let mut choices = Vec::new();
+        if let Some(current) = current {
+            for placement in spawn_placements(board, current, Rules::default()) {
+                choices.push((placement, next + 1, hold));
+            }
+        }
+
+        // hold swaps the piece in play with the held one, or with the next one when hold is empty
+        let (held, after_hold, hold_after) = match (hold, current) {
+            (Some(held), Some(current)) => (Some(held), next + 1, Some(current)),
+            (Some(held), None) => (Some(held), next, None),
+            (None, Some(current)) => (self.queue.get(next + 1).copied(), next + 2, Some(current)),
+            (None, None) => (None, next, None),
+        };
+
+        if let Some(held) = held.filter(|held| Some(*held) != current) {
+            for mut placement in spawn_placements(board, held, Rules::default()) {
+                placement.hold = true;
+                placement.inputs.insert(0, Input::Hold);
+                choices.push((placement, after_hold, hold_after));
+            }
+        }
+
+        choices.retain(|(placement, _, _)| placement.cells().iter().all(|&(_, y)| y >= BOARD_HEIGHT as usize - height));
+        choices
+    }
+}
+
+// whether everything on the board is within the bottom rows
+fn is_below(board: &Board, height: usize) -> bool {
+    (0..BOARD_HEIGHT as usize - height).all(|y| (0..BOARD_WIDTH as usize).all(|x| !board.is_occupied(x, y)))
+}
+
+// the bottom rows as bits, enough to tell boards apart while searching
+fn occupancy(board: &Board, height: usize) -> u64 {
+    let mut bits = 0;
+    for y in BOARD_HEIGHT as usize - height..BOARD_HEIGHT as usize {
+        for x in 0..BOARD_WIDTH as usize {
+            bits = bits << 1 | board.is_occupied(x, y) as u64;
+        }
+    }
+    bits
+}
+
+// a line clear removes a full row, so the empty cells under the height limit only ever go down by
+// four a piece and have to be filled exactly by the pieces there are
+fn can_fill(board: &Board, height: usize, pieces: usize) -> bool {
+    let empty = (BOARD_HEIGHT as usize - height..BOARD_HEIGHT as usize)
+        .flat_map(|y| (0..BOARD_WIDTH as usize).map(move |x| (x, y)))
+        .filter(|&(x, y)| !board.is_occupied(x, y))
+        .count();
+
+    empty % 4 == 0 && empty <= pieces * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: &[&str]) -> Board {
+        Board::from_rows(rows).unwrap()
+    }
+
+    fn is_clear(board: &Board) -> bool {
+        board.rows().iter().all(|row| row == "..........")
+    }
+
+    #[test]
+    fn the_last_piece_fills_the_gap() {
+        let solutions = perfect_clears(&board(&["XXXXXXXX..", "XXXXXXXX.."]), &[Type::O], None, 2, 10);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0][0].inputs, [Input::Right, Input::Right, Input::Right, Input::Right, Input::HardDrop]);
+    }
+
+    #[test]
+    fn every_solution_empties_the_board() {
+        let start = board(&["XXXXXX....", "XXXXXX....", "XXXXXX....", "XXXXXX...."]);
+        let solutions = perfect_clears(&start, &[Type::O, Type::O, Type::I, Type::I], None, 4, 100);
+        assert!(!solutions.is_empty());
+
+        for solution in &solutions {
+            assert_eq!(solution.len(), 4);
+            let end = solution.iter().fold(start.clone(), |board, placement| placement.apply(&board).0);
+            assert!(is_clear(&end));
+        }
+    }
+
+    // the six columns a perfect clear opener leaves open for the second bag
+    #[test]
+    fn the_opener_is_finished_with_a_bag() {
+        let start = board(&["XXXX......", "XXXX......", "XXXX......", "XXXX......"]);
+        let queue: Vec<Type> = "TILJOSZ".chars().filter_map(Type::from_letter).collect();
+        let solutions = perfect_clears(&start, &queue, None, 4, 1);
+        assert_eq!(solutions.len(), 1);
+
+        let end = solutions[0].iter().fold(start, |board, placement| placement.apply(&board).0);
+        assert!(is_clear(&end));
+    }
+
+    #[test]
+    fn the_limit_stops_the_search() {
+        let start = board(&["XXXXXX....", "XXXXXX....", "XXXXXX....", "XXXXXX...."]);
+        assert_eq!(perfect_clears(&start, &[Type::O, Type::O, Type::I, Type::I], None, 4, 3).len(), 3);
+    }
+
+    #[test]
+    fn too_few_pieces_or_rows_find_nothing() {
+        let start = board(&["XXXXXX....", "XXXXXX....", "XXXXXX....", "XXXXXX...."]);
+        assert!(perfect_clears(&start, &[Type::O, Type::I, Type::I], None, 4, 10).is_empty());
+        assert!(perfect_clears(&start, &[Type::O, Type::O, Type::I, Type::I], None, 3, 10).is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use termion::color::Rgb;
 
 const BLOCK_I: [[[u8; 4]; 4]; 4] = [
@@ -156,10 +157,10 @@ const BLOCK_L: [[[u8; 4]; 4]; 4] = [
         [0, 0, 0, 0],
     ],
     [
+        [0, 0, 0, 0],
         [1, 1, 1, 0],
         [1, 0, 0, 0],
         [0, 0, 0, 0],
-        [0, 0, 0, 0],
     ],
     [
         [1, 1, 0, 0],
@@ -169,7 +170,33 @@ const BLOCK_L: [[[u8; 4]; 4]; 4] = [
     ]
 ];
 
-#[derive(Copy, Clone)]
+// SRS wall kicks with y pointing down like on the board, the rows are 0->R, R->0, R->2, 2->R,
+// 2->L, L->2, L->0 and 0->L
+const KICKS_JLSTZ: [[(isize, isize); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+];
+
+const KICKS_I: [[(isize, isize); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+];
+
+const NO_KICKS: [(isize, isize); 5] = [(0, 0); 5];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Type {
     I,
     O,
@@ -180,6 +207,27 @@ pub enum Type {
     L,
 }
 
+impl Type {
+    pub const ALL: [Type; 7] = [Type::I, Type::O, Type::T, Type::S, Type::Z, Type::J, Type::L];
+
+    pub fn letter(self) -> char {
+        match self {
+            Type::I => 'I',
+            Type::O => 'O',
+            Type::T => 'T',
+            Type::S => 'S',
+            Type::Z => 'Z',
+            Type::J => 'J',
+            Type::L => 'L',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Type> {
+        Type::ALL.iter().copied().find(|ttype| ttype.letter() == letter.to_ascii_uppercase())
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Tetrimino {
     pub ttype: Type,
     pub state: usize,
@@ -206,6 +254,11 @@ impl Tetrimino {
         }
     }
 
+    /// The type whose color this is, if any, blocks on the board only remember their color.
+    pub fn type_of_color(rgb: Rgb) -> Option<Type> {
+        Type::ALL.iter().copied().find(|ttype| Self::color_of(*ttype) == rgb)
+    }
+
     pub fn block(&self) -> &[[u8; 4]; 4] {
         Self::block_of(self.ttype, self.state)
     }
@@ -222,12 +275,13 @@ impl Tetrimino {
         }
     }
 
+    // the states go 0, R, 2, L like in SRS, so clockwise is the next state
     pub fn rotate_clockwise(&self) -> Tetrimino {
         Tetrimino {
             ttype: self.ttype,
             state: match self.state {
-                0 => 3,
-                _ => self.state - 1
+                3 => 0,
+                _ => self.state + 1
             },
         }
     }
@@ -236,9 +290,29 @@ impl Tetrimino {
         Tetrimino {
             ttype: self.ttype,
             state: match self.state {
-                3 => 0,
-                _ => self.state + 1
+                0 => 3,
+                _ => self.state - 1
             },
         }
     }
+
+    /// Offsets to try in order when rotating from this state into the given one.
+    pub fn kicks(&self, to: usize) -> &'static [(isize, isize); 5] {
+        let index = match (self.state, to) {
+            (0, 1) => 0,
+            (1, 0) => 1,
+            (1, 2) => 2,
+            (2, 1) => 3,
+            (2, 3) => 4,
+            (3, 2) => 5,
+            (3, 0) => 6,
+            _ => 7,
+        };
+
+        match self.ttype {
+            Type::O => &NO_KICKS,
+            Type::I => &KICKS_I[index],
+            _ => &KICKS_JLSTZ[index],
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::io::{Read, Result, Write};
+use std::thread;
+use std::time::Duration;
+
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Mode, Rules};
+use crate::game::view::{View, VIEW_WIDTH};
+
+/// Two players on one keyboard, side by side, sending each other garbage until one tops out.
+pub struct Versus<R: Read, W: Write> {
+    stdin: Keys<R>,
+    stdout: W,
+    rules: Rules,
+    players: Vec<Player>,
+    round_over: bool,
+}
+
+struct Player {
+    engine: Engine,
+    view: View,
+    wins: usize,
+}
+
+impl<R: Read, W: Write> Versus<R, W> {
+    pub fn new(r: R, w: W, rules: Rules) -> Versus<R, RawTerminal<W>> {
+        let players = vec![Controls::left_hand(), Controls::right_hand()]
+            .into_iter()
+            .enumerate()
+            .map(|(i, controls)| {
+                let mut view = View::new(1 + i as u16 * (VIEW_WIDTH + 1), 1, controls);
+                view.set_quit_key(Some("esc"));
+                Player { engine: Engine::new(rules), view, wins: 0 }
+            })
+            .collect();
+
+        let mut versus = Versus {
+            stdin: r.keys(),
+            stdout: w.into_raw_mode().unwrap(),
+            rules,
+            players,
+            round_over: false,
+        };
+        versus.new_round();
+        versus
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        for player in self.players.iter_mut() {
+            player.view.set_animations(animations);
+        }
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
+
+        'main: loop {
+            thread::sleep(Duration::from_millis(50));
+
+            // both players can press keys in the same frame, so everything waiting is read
+            while let Some(Ok(key)) = self.stdin.next() {
+                match key {
+                    Key::Esc => break 'main,
+                    Key::Char('\n') if self.round_over => self.new_round(),
+                    _ if self.round_over => {}
+                    key => {
+                        for player in self.players.iter_mut() {
+                            if let Some(input) = player.view.controls().input(key) {
+                                player.engine.input(input);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if !self.round_over {
+                for player in self.players.iter_mut() {
+                    player.engine.tick(Duration::from_millis(50));
+                }
+                self.send_garbage();
+                self.check_round_over();
+            }
+
+            for player in self.players.iter_mut() {
+                player.view.draw(&mut self.stdout, &player.engine)?;
+            }
+            self.stdout.flush()?;
+        }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+
+        Ok(())
+    }
+
+    // both players get the same pieces, so it's down to how they're played
+    fn new_round(&mut self) {
+        let seed = rand::random();
+        for player in self.players.iter_mut() {
+            player.engine = Engine::with_seed(self.rules, Mode::Marathon, seed);
+            player.view.reset();
+        }
+        self.round_over = false;
+    }
+
+    fn send_garbage(&mut self) {
+        let attacks: Vec<usize> = self.players.iter_mut().map(|player| player.engine.take_attack()).collect();
+        for (i, attack) in attacks.into_iter().enumerate() {
+            let opponent = &mut self.players[1 - i].engine;
+            if attack > 0 {
+                opponent.receive_garbage(attack);
+            }
+        }
+    }
+
+    fn check_round_over(&mut self) {
+        if !self.players.iter().any(|player| player.engine.is_lost()) {
+            return;
+        }
+
+        // both can top out in the same frame, then nobody wins the round
+        let draw = self.players.iter().all(|player| player.engine.is_lost());
+        for player in self.players.iter_mut() {
+            if !player.engine.is_lost() {
+                player.wins += 1;
+            }
+        }
+
+        let score = format!("{} - {}", self.players[0].wins, self.players[1].wins);
+        for player in self.players.iter_mut() {
+            let result = match (draw, player.engine.is_lost()) {
+                (true, _) => "DRAW",
+                (false, true) => "TOP OUT",
+                (false, false) => "WINNER",
+            };
+            player.view.set_banner(vec![result.to_string(), score.clone(), "enter: rematch".to_string()]);
+        }
+
+        self.round_over = true;
+    }
+}
@@ -0,0 +1,447 @@
+use std::io::{Result, Write};
+use std::time::Duration;
+
+use termion::{cursor, style};
+use termion::color::{Bg, Fg, Rgb};
+
+use crate::graphics::*;
+use crate::bot::{Bot, Heuristic, Placement};
+use crate::game::controls::Controls;
+use crate::game::engine::{Block, Engine, Input, Mode, BOARD_HEIGHT, BOARD_WIDTH, SPRINT_LINES, ULTRA_TIME};
+use crate::game::tetrimino::{Tetrimino, Type};
+
+const LEFT_PANEL_WIDTH: u16 = 21;
+
+const SCORE_WINDOW_HEIGHT: u16 = 13;
+const HELP_WINDOW_HEIGHT: u16 = 14;
+
+const RIGHT_PANEL_WIDTH: u16 = 12;
+const NEXT_WINDOW_HEIGHT: u16 = 10;
+const HELD_WINDOW_HEIGHT: u16 = 10;
+
+const LOCK_FLASH_COLOR: Rgb = Rgb(255, 255, 255);
+const GARBAGE_METER_COLOR: Rgb = Rgb(220, 40, 40);
+
+/// How wide and tall one player's panels and board are, for putting views side by side.
+pub const VIEW_WIDTH: u16 = LEFT_PANEL_WIDTH + BOARD_WIDTH * 2 + 4 + RIGHT_PANEL_WIDTH;
+pub const VIEW_HEIGHT: u16 = SCORE_WINDOW_HEIGHT + 1 + HELP_WINDOW_HEIGHT;
+
+/// Draws one engine's board, panels and help at some spot on the terminal.
+pub struct View {
+    x: u16,
+    y: u16,
+    controls: Controls,
+
+    // the keys listed for hints and quitting, they aren't listed when there's none
+    hint_key: Option<&'static str>,
+    quit_key: Option<&'static str>,
+
+    // line clear and lock animations are still timed by the engine when this is off,
+    // they just aren't drawn
+    animations: bool,
+
+    // shows the fewest inputs for the last piece which wasn't placed with good finesse
+    finesse_hints: bool,
+
+    // outlines where the built-in bot would put the current piece, worked out once per piece
+    hints: bool,
+    hint: Option<(HintKey, Option<Placement>)>,
+
+    // shown over the board, like the result at the end of a round
+    banner: Vec<String>,
+}
+
+// a new piece is in play whenever another one has locked or hold was used
+type HintKey = (usize, Option<Type>);
+
+impl View {
+    pub fn new(x: u16, y: u16, controls: Controls) -> Self {
+        View {
+            x,
+            y,
+            controls,
+            hint_key: None,
+            quit_key: Some("q"),
+            animations: true,
+            finesse_hints: false,
+            hints: false,
+            hint: None,
+            banner: Vec::new(),
+        }
+    }
+
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    pub fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    pub fn set_hint_key(&mut self, key: Option<&'static str>) {
+        self.hint_key = key;
+    }
+
+    pub fn set_quit_key(&mut self, key: Option<&'static str>) {
+        self.quit_key = key;
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        self.animations = animations;
+    }
+
+    pub fn set_finesse_hints(&mut self, hints: bool) {
+        self.finesse_hints = hints;
+    }
+
+    pub fn finesse_hints(&self) -> bool {
+        self.finesse_hints
+    }
+
+    pub fn set_banner(&mut self, banner: Vec<String>) {
+        self.banner = banner;
+    }
+
+    pub fn toggle_hints(&mut self) {
+        self.hints = !self.hints && self.hint_key.is_some();
+    }
+
+    /// Forgets everything about the last game, for when another one starts in the same view.
+    pub fn reset(&mut self) {
+        self.hint = None;
+        self.banner.clear();
+    }
+
+    pub fn draw<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        self.draw_player_score(out, engine)?;
+        self.draw_help(out)?;
+        self.draw_board(out, engine)?;
+        self.draw_garbage_meter(out, engine)?;
+        self.draw_next(out, engine)?;
+        self.draw_held(out, engine)
+    }
+
+    fn current_hint(&mut self, engine: &Engine) -> Option<Placement> {
+        if !self.hints {
+            return None;
+        }
+
+        engine.active()?;
+        let key = (engine.stats().pieces, engine.held_type());
+        match &self.hint {
+            Some((hint_key, hint)) if *hint_key == key => hint.clone(),
+            _ => {
+                let hint = Heuristic::default().suggest(engine);
+                self.hint = Some((key, hint.clone()));
+                hint
+            }
+        }
+    }
+
+    fn draw_player_score<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        let (x, y) = (self.x, self.y);
+        create_window(out, x, y, LEFT_PANEL_WIDTH, SCORE_WINDOW_HEIGHT)?;
+        write!(out, "{}Score", cursor::Goto(x + 8, y + 2))?;
+        let stats = engine.stats();
+        write!(out, "{}score: {:06} ", cursor::Goto(x + 3, y + 4), stats.score)?;
+        write!(out, "{}lines: {:04} ", cursor::Goto(x + 3, y + 5), stats.lines)?;
+        write!(out, "{}pcs:   {:04} ", cursor::Goto(x + 3, y + 6), stats.perfect_clears)?;
+
+        let faults = engine.finesse()
+            .map(|finesse| format!("faults:{:04}", finesse.faults))
+            .unwrap_or_default();
+        write!(out, "{}{:<13}", cursor::Goto(x + 3, y + 7), faults)?;
+
+        // drills, puzzles, digs and races never happen at once, they share a line
+        let objective = match (engine.drill(), engine.goal(), engine.dig()) {
+            (Some(drill), _, _) => format!("hits:  {:04}/{:04}", drill.hits, drill.hits + drill.misses),
+            (_, Some(goal), _) => goal.description(),
+            (_, _, Some(dig)) => format!("dug {}/{} {}", dig.dug, dig.target, clock(engine.played())),
+            _ if engine.mode() == Mode::Sprint => format!("{}/{} {}", stats.lines.min(SPRINT_LINES), SPRINT_LINES, clock(engine.played())),
+            _ if engine.mode() == Mode::Ultra => format!("left {}", clock(ULTRA_TIME.saturating_sub(engine.played()))),
+            _ => engine.comment().unwrap_or_default().to_string(),
+        };
+        write!(out, "{}{:<16.16}", cursor::Goto(x + 3, y + 8), objective)?;
+
+        // callouts, cleared first since they're transient
+        for i in 0..2 {
+            let text = engine.callouts().get(i).map(String::as_str).unwrap_or("");
+            write!(out, "{}{:^17}", cursor::Goto(x + 2, y + 9 + i as u16), text)?;
+        }
+
+        let hint = match engine.finesse().and_then(|finesse| finesse.last_fault.as_ref()) {
+            Some(fault) if self.finesse_hints => fault.optimal.iter().map(|input| input_label(*input)).collect::<Vec<_>>().join(" "),
+            _ => String::new(),
+        };
+        write!(out, "{}{:^17}", cursor::Goto(x + 2, y + 11), hint.chars().take(17).collect::<String>())?;
+
+        Ok(())
+    }
+
+    fn draw_help<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        let (x, y) = (self.x, self.y + SCORE_WINDOW_HEIGHT + 1);
+        create_window(out, x, y, LEFT_PANEL_WIDTH, HELP_WINDOW_HEIGHT)?;
+        write!(out, "{}Ctrls", cursor::Goto(x + 8, y + 2))?;
+        for (i, (action, keys)) in self.controls.help().iter().enumerate() {
+            write!(out, "{}{:<6} {}", cursor::Goto(x + 3, y + 4 + i as u16), action, keys)?;
+        }
+        if let Some(key) = self.hint_key {
+            write!(out, "{}hint   {}", cursor::Goto(x + 3, y + 10), key)?;
+        }
+        if let Some(key) = self.quit_key {
+            write!(out, "{}quit   {}", cursor::Goto(x + 3, y + 11), key)?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_board<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        let width = engine.board().width() as u16;
+        let (init_x, init_y) = (self.x + LEFT_PANEL_WIDTH + 1, self.y);
+        create_window(out, init_x, init_y, (width * 2) + 2, BOARD_HEIGHT + 2)?;
+
+        // draw the board
+        let (init_x, init_y) = (init_x + 1, init_y + 1);
+        let banner = |out: &mut W, lines: &[&str]| draw_banner(out, init_x, init_y, width, lines);
+        for (y, row) in engine.board().blocks().enumerate() {
+            for (x, col) in row.iter().enumerate() {
+                let goto = cursor::Goto(init_x + (x * 2) as u16, init_y + y as u16);
+                match self.animated_block(engine, x, y, *col) {
+                    Block::Free => write!(out, "{}{}  ", goto, style::Reset)?,
+                    Block::Occupied(rgb) => write!(out, "{}{}  ", goto, Bg(rgb))?,
+                }
+            }
+        }
+
+        if let Some(target) = engine.drill().and_then(|drill| drill.target.as_ref()) {
+            let color = target.tetrimino.color();
+            draw_outline(out, engine, init_x, init_y, &target.cells(), color)?;
+        }
+
+        if let Some(hint) = self.current_hint(engine) {
+            draw_outline(out, engine, init_x, init_y, &hint.cells(), hint.tetrimino.color())?;
+        }
+
+        // draw the falling tetriminos, doesn't need to care about bounds, we can't even move outside of bound!
+        for active in (0..engine.players()).filter_map(|player| engine.player_active(player)) {
+            let (x, y) = (init_x as isize + active.x * 2, init_y as isize + active.y);
+            let (ttype, state) = (active.tetrimino.ttype, active.tetrimino.state);
+            draw_tetrimino(out, x, y, 65535, 65535, ttype, state)?;
+        }
+
+        // drawn over the board so it can't be missed
+        if engine.showing_perfect_clear() {
+            banner(out, &["PERFECT CLEAR"])?;
+        }
+
+        if engine.mode() == Mode::Puzzle && engine.is_over() {
+            banner(out, &if engine.is_won() { ["SOLVED", "enter: next"] } else { ["FAILED", "enter: retry"] })?;
+        }
+
+        if engine.is_won() {
+            let time = clock(engine.played());
+            let pieces = format!("{} pieces", engine.stats().pieces);
+            let score = format!("{} points", engine.stats().score);
+            match engine.mode() {
+                Mode::Dig => banner(out, &["DUG OUT", &time, &pieces])?,
+                Mode::Sprint => banner(out, &["FINISHED", &time, &pieces])?,
+                Mode::Ultra => banner(out, &["TIME UP", &score, &pieces])?,
+                _ => {}
+            }
+        }
+
+        if !self.banner.is_empty() {
+            let lines: Vec<&str> = self.banner.iter().map(String::as_str).collect();
+            banner(out, &lines)?;
+        }
+
+        Ok(())
+    }
+
+    // incoming garbage stacks up from the bottom in the column between the left panel and the board
+    fn draw_garbage_meter<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        let (x, y) = (self.x + LEFT_PANEL_WIDTH, self.y + 1);
+        let incoming = engine.incoming_garbage();
+        for row in 0..BOARD_HEIGHT {
+            let goto = cursor::Goto(x, y + BOARD_HEIGHT - 1 - row);
+            if (row as usize) < incoming {
+                write!(out, "{}{} {}", goto, Bg(GARBAGE_METER_COLOR), style::Reset)?;
+            } else {
+                write!(out, "{}{} ", goto, style::Reset)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // cleared rows dissolve from the middle outwards and newly locked cells flash
+    fn animated_block(&self, engine: &Engine, x: usize, y: usize, block: Block) -> Block {
+        if !self.animations {
+            return block;
+        }
+
+        if let Some((rows, progress)) = engine.clearing_rows() {
+            let half = engine.board().width() as f32 / 2.0;
+            let distance = (x as f32 + 0.5 - half).abs();
+            if rows.contains(&y) && distance < progress * half {
+                return Block::Free;
+            }
+        }
+
+        if engine.lock_flash().contains(&(x, y)) {
+            return Block::Occupied(LOCK_FLASH_COLOR);
+        }
+
+        block
+    }
+
+    fn draw_next<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        let (x, y) = (right_panel_x(self.x, engine), self.y);
+        create_window(out, x, y, RIGHT_PANEL_WIDTH, NEXT_WINDOW_HEIGHT)?;
+        write!(out, "{}Next", cursor::Goto(x + 4, y + 2))?;
+
+        for i in 0..4 {
+            write!(out, "{}        ", cursor::Goto(x + 2, y + i + 4))?; // clear first
+        }
+
+        if let Some(next) = engine.next_type() {
+            draw_tetrimino(out, x as isize + 2, y as isize + 4, x as isize + 9, y as isize + 9, next, 0)?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_held<W: Write>(&mut self, out: &mut W, engine: &Engine) -> Result<()> {
+        let (x, y) = (right_panel_x(self.x, engine), self.y + NEXT_WINDOW_HEIGHT + 2);
+        create_window(out, x, y, RIGHT_PANEL_WIDTH, HELD_WINDOW_HEIGHT)?;
+        write!(out, "{}Held", cursor::Goto(x + 4, y + 2))?;
+
+        for i in 0..4 {
+            write!(out, "{}        ", cursor::Goto(x + 2, y + i + 4))?; // clear first
+        }
+
+        if let Some(held) = engine.held_type() {
+            draw_tetrimino(out, x as isize + 2, y as isize + 4, x as isize + 9, y as isize + 9, held, 0)?;
+        }
+
+        Ok(())
+    }
+}
+
+// the next and held pieces go to the right of the board, however wide it is
+fn right_panel_x(x: u16, engine: &Engine) -> u16 {
+    x + LEFT_PANEL_WIDTH + engine.board().width() as u16 * 2 + 4
+}
+
+/// Minutes, seconds and tenths, for the results of races.
+pub fn clock(time: Duration) -> String {
+    let tenths = time.as_millis() / 100;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+// outlines of cells rather than solid blocks, for spots the piece should go into
+fn draw_outline<W: Write>(out: &mut W, engine: &Engine, init_x: u16, init_y: u16, cells: &[(usize, usize)], color: Rgb) -> Result<()> {
+    for &(x, y) in cells {
+        if x < engine.board().width() && y < BOARD_HEIGHT as usize && !engine.board().is_occupied(x, y) {
+            let goto = cursor::Goto(init_x + (x * 2) as u16, init_y + y as u16);
+            write!(out, "{}{}[]{}", goto, Fg(color), style::Reset)?;
+        }
+    }
+
+    Ok(())
+}
+
+// a few lines of bold text across the middle of the board, with a blank line above and below
+fn draw_banner<W: Write>(out: &mut W, init_x: u16, init_y: u16, width: u16, lines: &[&str]) -> Result<()> {
+    let (y, width) = (init_y + BOARD_HEIGHT / 2 - 1, width as usize * 2);
+    write!(out, "{}{}{:^width$}", cursor::Goto(init_x, y), style::Bold, "", width = width)?;
+    for (i, line) in lines.iter().enumerate() {
+        write!(out, "{}{:^width$}", cursor::Goto(init_x, y + 1 + i as u16), line, width = width)?;
+    }
+    write!(out, "{}{:^width$}{}", cursor::Goto(init_x, y + 1 + lines.len() as u16), "", style::Reset, width = width)
+}
+
+fn draw_tetrimino<W: Write>(out: &mut W, x: isize, y: isize, bound_x: isize, bound_y: isize, ttype: Type, state: usize) -> Result<()> {
+    let block = Tetrimino::block_of(ttype, state);
+    let color = Tetrimino::color_of(ttype);
+
+    for (yi, row) in block.iter().enumerate() {
+        for (xi, col) in row.iter().enumerate() {
+            let x = x + xi as isize * 2;
+            let y = y + yi as isize;
+
+            if *col == 1 && x >= 0 && x < bound_x && y >= 0 && y <= bound_y {
+                write!(out, "{}{}  {}", cursor::Goto(x as u16, y as u16), Bg(color), style::Reset)?;
+            }
+        }
+    }
+
+    write!(out, "{}", style::Reset)
+}
+
+fn input_label(input: Input) -> &'static str {
+    match input {
+        Input::Left => "←",
+        Input::Right => "→",
+        Input::SoftDrop => "↓",
+        Input::SonicDrop => "⇊",
+        Input::HardDrop => "␣",
+        Input::RotateClockwise => "x",
+        Input::RotateCounterClockwise => "z",
+        Input::Hold => "c",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::Rules;
+
+    fn view() -> View {
+        let mut view = View::new(1, 1, Controls::single());
+        view.set_hint_key(Some("h"));
+        view
+    }
+
+    #[test]
+    fn hints_are_only_shown_when_allowed() {
+        let engine = Engine::with_seed(Rules::default(), Mode::Marathon, 1);
+        let mut view = View::new(1, 1, Controls::single());
+        view.toggle_hints();
+        assert!(view.current_hint(&engine).is_none());
+
+        let mut view = self::view();
+        assert!(view.current_hint(&engine).is_none());
+        view.toggle_hints();
+        assert!(view.current_hint(&engine).is_some());
+        view.toggle_hints();
+        assert!(view.current_hint(&engine).is_none());
+    }
+
+    #[test]
+    fn the_hint_is_worked_out_again_for_each_piece() {
+        let mut engine = Engine::with_seed(Rules::default(), Mode::Marathon, 1);
+        let mut view = view();
+        view.toggle_hints();
+
+        let hint = view.current_hint(&engine).unwrap();
+        assert_eq!(hint.cells(), Heuristic::default().suggest(&engine).unwrap().cells());
+        assert_eq!(view.hint.as_ref().unwrap().0, (0, None));
+
+        engine.input(Input::Hold);
+        let held = view.current_hint(&engine).unwrap();
+        assert_eq!(view.hint.as_ref().unwrap().0, (0, engine.held_type()));
+        assert_eq!(held.cells(), Heuristic::default().suggest(&engine).unwrap().cells());
+
+        // nothing is suggested while waiting for the next piece
+        engine.input(Input::HardDrop);
+        assert!(view.current_hint(&engine).is_none());
+        engine.tick(Rules::default().entry_delay);
+        view.current_hint(&engine).unwrap();
+        assert_eq!(view.hint.as_ref().unwrap().0, (1, engine.held_type()));
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::{Read, Result, Write};
+use std::thread;
+use std::time::Duration;
+
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+
+use crate::game::controls::Controls;
+use crate::game::engine::{Engine, Rules};
+use crate::game::snapshot::Snapshot;
+use crate::game::view::View;
+use crate::net::Connection;
+
+/// A read-only mirror of a game streamed from somewhere else, nothing can be played here.
+pub struct Watch<R: Read, W: Write> {
+    stdin: Keys<R>,
+    stdout: W,
+    connection: Connection<Snapshot>,
+    engine: Engine,
+    view: View,
+}
+
+impl<R: Read, W: Write> Watch<R, W> {
+    pub fn new(r: R, w: W, connection: Connection<Snapshot>) -> Watch<R, RawTerminal<W>> {
+        Watch {
+            stdin: r.keys(),
+            stdout: w.into_raw_mode().unwrap(),
+            connection,
+            engine: Engine::new(Rules::default()),
+            view: View::new(1, 1, Controls::none()),
+        }
+    }
+
+    pub fn set_animations(&mut self, animations: bool) {
+        self.view.set_animations(animations);
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        write!(&mut self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Hide)?;
+
+        let mut ended = false;
+        loop {
+            thread::sleep(Duration::from_millis(50));
+
+            if let Some(Ok(Key::Char('q'))) = self.stdin.next() {
+                break;
+            }
+
+            // everything which arrived since the last frame is mirrored, the newest is what's drawn
+            while !ended {
+                match self.connection.poll() {
+                    Ok(Some(snapshot)) => {
+                        if self.engine.mirror(&snapshot).is_err() {
+                            ended = true;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(_) => ended = true,
+                }
+            }
+            if ended {
+                self.view.set_banner(vec!["STREAM ENDED".to_string()]);
+            }
+
+            self.view.draw(&mut self.stdout, &self.engine)?;
+            self.stdout.flush()?;
+        }
+        write!(self.stdout, "{}{}{}{}", clear::All, style::Reset, cursor::Goto(1, 1), cursor::Show)?;
+
+        Ok(())
+    }
+}
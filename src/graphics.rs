@@ -2,12 +2,12 @@ use std::io::{Result, Write};
 
 use termion::cursor;
 
-const TOP_LEFT_CORNER: &'static str = "╔";
-const TOP_RIGHT_CORNER: &'static str = "╗";
-const BOTTOM_LEFT_CORNER: &'static str = "╚";
-const BOTTOM_RIGHT_CORNER: &'static str = "╝";
-const VERTICAL_WALL: &'static str = "║";
-const HORIZONTAL_WALL: &'static str = "═";
+const TOP_LEFT_CORNER: &str = "╔";
+const TOP_RIGHT_CORNER: &str = "╗";
+const BOTTOM_LEFT_CORNER: &str = "╚";
+const BOTTOM_RIGHT_CORNER: &str = "╝";
+const VERTICAL_WALL: &str = "║";
+const HORIZONTAL_WALL: &str = "═";
 
 pub fn create_window<W>(w: &mut W, x: u16, y: u16, width: u16, height: u16) -> Result<()>
     where W: Write
@@ -18,13 +18,13 @@ pub fn create_window<W>(w: &mut W, x: u16, y: u16, width: u16, height: u16) -> R
     write!(w, "{}{}", cursor::Goto(x + width - 1, y + height - 1), BOTTOM_RIGHT_CORNER)?;
 
     for i in 1..width - 1 {
-        write!(w, "{}{}", cursor::Goto(x + i as u16, y), HORIZONTAL_WALL)?;
-        write!(w, "{}{}", cursor::Goto(x + i as u16, y + height - 1), HORIZONTAL_WALL)?;
+        write!(w, "{}{}", cursor::Goto(x + i, y), HORIZONTAL_WALL)?;
+        write!(w, "{}{}", cursor::Goto(x + i, y + height - 1), HORIZONTAL_WALL)?;
     }
 
     for i in 1..height - 1 {
-        write!(w, "{}{}", cursor::Goto(x, y + i as u16), VERTICAL_WALL)?;
-        write!(w, "{}{}", cursor::Goto(x + width - 1, y + i as u16), VERTICAL_WALL)?;
+        write!(w, "{}{}", cursor::Goto(x, y + i), VERTICAL_WALL)?;
+        write!(w, "{}{}", cursor::Goto(x + width - 1, y + i), VERTICAL_WALL)?;
     }
 
     Ok(())
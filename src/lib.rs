@@ -1,2 +1,10 @@
+pub mod args;
+pub mod bot;
+pub mod cast;
+pub mod env;
 pub mod game;
-pub mod graphics;
\ No newline at end of file
+pub mod graphics;
+pub mod net;
+pub mod relay;
+pub mod sim;
+pub mod storage;
\ No newline at end of file
@@ -1,8 +1,684 @@
-use std::io::stdout;
-use tetrust::game::Game;
+use std::env;
+use std::io::{self, stdout, Read, Stdout, Write};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use tetrust::args::{parse_holes, parse_randomizer};
+use tetrust::bot::{Bot, ExternalBot, Heuristic};
+use tetrust::cast::{self, Cast, Recorder};
+use tetrust::game::{Coop, Game, Lobby, Online, Versus, Watch};
+use tetrust::game::daily::{Challenge, Date, Entry, Leaderboard};
+use tetrust::game::engine::{Engine, Mode, Rules};
+use tetrust::game::fumen::{self, Page};
+use tetrust::game::online::Role;
+use tetrust::game::puzzle::{Goal, Pack, Progress, Puzzle};
+use tetrust::game::replay::{Ghost, Replay};
+use tetrust::game::save::Save;
+use tetrust::game::solver::{perfect_clears, MAX_HEIGHT};
+use tetrust::game::view::clock;
+use tetrust::net::{Broadcast, Connection};
 use termion::async_stdin;
 
+const USAGE: &str = "usage: tetrust [--mode marathon|drill|dig|sprint|ultra | --daily | --puzzle FILE | --fumen DATA | --versus | --coop |
+                --host ADDR | --join ADDR | --server ADDR --room NAME] [--name NAME]
+               [--dig-rows N] [--dig-target N] [--lock-delay MS] [--line-clear-delay MS] [--lock-flash MS] [--entry-delay MS]
+               [--randomizer bag|random] [--garbage clean|messy|cheese] [--no-hold] [--seed N] [--no-animations]
+               [--finesse] [--finesse-hints] [--ai | --bot COMMAND] [--stream ADDR]
+               [--record FILE] [--ghost FILE|best] [--cast FILE]
+       tetrust watch ADDR
+       tetrust daily [--date YYYY-MM-DD] [--merge FILE]..
+       tetrust fumen REPLAY [--last]
+       tetrust cast REPLAY FILE
+       tetrust solve [--board ROW,ROW,.. | --puzzle FILE | --fumen DATA] --queue PIECES [--hold PIECE] [--height N] [--limit N]";
+
+struct Options {
+    rules: Rules,
+    mode: Mode,
+    seed: Option<u64>,
+    dig_rows: usize,
+    dig_target: usize,
+    daily: bool,
+    puzzle: Option<String>,
+    fumen: Option<String>,
+    versus: bool,
+    coop: bool,
+    online: Option<(Role, String)>,
+    stream: Option<String>,
+    record: Option<String>,
+    cast: Option<String>,
+    ghost: Option<String>,
+    server: Option<String>,
+    room: Option<String>,
+    name: Option<String>,
+    animations: bool,
+    ai: bool,
+    bot: Option<String>,
+    finesse: bool,
+    finesse_hints: bool,
+
+    // anything was asked for which makes a particular game, rather than just how it's shown
+    particular: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        rules: Rules::default(),
+        mode: Mode::Marathon,
+        seed: None,
+        dig_rows: 10,
+        dig_target: 40,
+        daily: false,
+        puzzle: None,
+        fumen: None,
+        versus: false,
+        coop: false,
+        online: None,
+        stream: None,
+        record: None,
+        cast: None,
+        ghost: None,
+        server: None,
+        room: None,
+        name: None,
+        animations: true,
+        ai: false,
+        bot: None,
+        finesse: false,
+        finesse_hints: false,
+        particular: false,
+    };
+    let mut args = env::args().skip(1);
+
+    // the last flag which picks some other game, or other rules, than the daily challenge's
+    let mut not_daily = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lock-delay" => options.rules.lock_delay = parse_millis(&arg, args.next())?,
+            "--line-clear-delay" => options.rules.line_clear_delay = parse_millis(&arg, args.next())?,
+            "--lock-flash" => options.rules.lock_flash = parse_millis(&arg, args.next())?,
+            "--entry-delay" => options.rules.entry_delay = parse_millis(&arg, args.next())?,
+            "--randomizer" => options.rules.randomizer = parse_randomizer(args.next())?,
+            "--garbage" => options.rules.garbage = parse_holes(args.next())?,
+            "--no-hold" => options.rules.hold = false,
+            "--seed" => options.seed = Some(args.next().and_then(|v| v.parse().ok()).ok_or("--seed expects a number")?),
+            "--dig-rows" => options.dig_rows = args.next().and_then(|v| v.parse().ok()).ok_or("--dig-rows expects a number of rows")?,
+            "--dig-target" => options.dig_target = args.next().and_then(|v| v.parse().ok()).ok_or("--dig-target expects a number of lines")?,
+            "--no-animations" => options.animations = false,
+            "--mode" => options.mode = parse_mode(args.next())?,
+            "--daily" => options.daily = true,
+            "--puzzle" => options.puzzle = Some(args.next().ok_or("--puzzle expects a puzzle file")?),
+            "--fumen" => options.fumen = Some(args.next().ok_or("--fumen expects a v115 fumen")?),
+            "--versus" => options.versus = true,
+            "--coop" => options.coop = true,
+            "--host" => options.online = Some((Role::Host, args.next().ok_or("--host expects an address to listen on")?)),
+            "--join" => options.online = Some((Role::Guest, args.next().ok_or("--join expects an address to connect to")?)),
+            "--finesse" => options.finesse = true,
+            "--finesse-hints" => options.finesse_hints = true,
+            "--server" => options.server = Some(args.next().ok_or("--server expects the address of a tetrust-server")?),
+            "--room" => options.room = Some(args.next().ok_or("--room expects a room name")?),
+            "--name" => options.name = Some(args.next().ok_or("--name expects a player name")?),
+            "--stream" => options.stream = Some(args.next().ok_or("--stream expects an address to listen on")?),
+            "--record" => options.record = Some(args.next().ok_or("--record expects a file to save the replay to")?),
+            "--cast" => options.cast = Some(args.next().ok_or("--cast expects a file to record the terminal to")?),
+            "--ghost" => options.ghost = Some(args.next().ok_or("--ghost expects a replay file or best")?),
+            "--ai" => options.ai = true,
+            "--bot" => options.bot = Some(args.next().ok_or("--bot expects a command to run")?),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+        if !matches!(arg.as_str(), "--no-animations" | "--name" | "--stream" | "--cast") {
+            options.particular = true;
+        }
+        if !matches!(arg.as_str(), "--daily" | "--no-animations" | "--name" | "--stream" | "--cast" | "--record" | "--finesse" | "--finesse-hints" | "--ai" | "--bot" | "--ghost") {
+            not_daily = Some(arg);
+        }
+    }
+
+    // the leaderboard is for people, and for the challenge's own seed and rules
+    if options.daily && (options.ai || options.bot.is_some()) {
+        return Err("--daily can't be played by --ai or --bot".to_string());
+    }
+    if options.daily && options.ghost.is_some() {
+        return Err("--daily can't race a --ghost".to_string());
+    }
+    // the challenge has its own mode, seed and rules, anything else wouldn't be the day's game
+    if let (true, Some(arg)) = (options.daily, not_daily) {
+        return Err(format!("--daily plays the day's challenge, it can't be played with {}", arg));
+    }
+    // only the single player game is streamed, watchers are shown one ordinary board
+    if options.stream.is_some() && (options.versus || options.coop || options.online.is_some() || options.server.is_some()) {
+        return Err("--stream only streams a single player game, not --versus, --coop or online games".to_string());
+    }
+    // a fumen only has a board, the other modes need their own start or are kept as races
+    if options.fumen.is_some() && options.mode != Mode::Marathon {
+        return Err("--fumen plays a marathon, it can't be played with another --mode".to_string());
+    }
+
+    Ok(options)
+}
+
+fn parse_mode(value: Option<String>) -> Result<Mode, String> {
+    match value.as_deref() {
+        Some("marathon") => Ok(Mode::Marathon),
+        Some("drill") => Ok(Mode::Drill),
+        Some("dig") => Ok(Mode::Dig),
+        Some("sprint") => Ok(Mode::Sprint),
+        Some("ultra") => Ok(Mode::Ultra),
+        _ => Err("--mode expects marathon, drill, dig, sprint or ultra".to_string()),
+    }
+}
+
+fn parse_millis(arg: &str, value: Option<String>) -> Result<Duration, String> {
+    value.and_then(|v| v.parse().ok())
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("{} expects a number of milliseconds", arg))
+}
+
+// finds perfect clears for a board and queue and prints the inputs for each piece
+fn solve(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut puzzle = Puzzle { name: "solve".to_string(), board: Vec::new(), queue: String::new(), hold: None, goal: Goal::PerfectClear };
+    let mut height = 4;
+    let mut limit = 10;
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--board" => puzzle.board = args.next().ok_or("--board expects rows")?.split(',').map(String::from).collect(),
+            "--puzzle" => {
+                let path = args.next().ok_or("--puzzle expects a puzzle file")?;
+                let pack = Pack::load(&path).map_err(|err| format!("couldn't load the puzzle {}: {}", path, err))?;
+                puzzle = pack.puzzles.into_iter().next().ok_or("the puzzle file has no puzzles")?;
+            }
+            "--fumen" => puzzle.board = first_page(args.next())?.board()?.rows(),
+            "--queue" => puzzle.queue = args.next().ok_or("--queue expects pieces")?,
+            "--hold" => puzzle.hold = args.next().and_then(|hold| hold.chars().next()),
+            "--height" => height = args.next().and_then(|v| v.parse().ok()).filter(|h| (1..=MAX_HEIGHT).contains(h))
+                .ok_or_else(|| format!("--height expects 1 to {} rows", MAX_HEIGHT))?,
+            "--limit" => limit = args.next().and_then(|v| v.parse().ok()).ok_or("--limit expects a number")?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let board = puzzle.board()?;
+    let queue = puzzle.queue()?;
+    let solutions = perfect_clears(&board, &queue, puzzle.hold()?, height, limit);
+
+    println!("{} perfect clears", solutions.len());
+    for (i, solution) in solutions.iter().enumerate() {
+        println!("\n{}.", i + 1);
+        for placement in solution {
+            let inputs: Vec<String> = placement.inputs.iter().map(|input| format!("{:?}", input)).collect();
+            println!("  {}  {}", placement.tetrimino.ttype.letter(), inputs.join(" "));
+        }
+    }
+
+    Ok(())
+}
+
 fn main() {
-    let mut game = Game::new(1, 1, async_stdin(), stdout());
-    game.start().unwrap();
-}
\ No newline at end of file
+    if env::args().nth(1).as_deref() == Some("solve") {
+        if let Err(err) = solve(env::args().skip(2)) {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if env::args().nth(1).as_deref() == Some("fumen") {
+        if let Err(err) = export_fumen(env::args().skip(2)) {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if env::args().nth(1).as_deref() == Some("cast") {
+        if let Err(err) = render_cast(env::args().skip(2)) {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if env::args().nth(1).as_deref() == Some("daily") {
+        if let Err(err) = daily(env::args().skip(2)) {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+        return;
+    }
+
+    if env::args().nth(1).as_deref() == Some("watch") {
+        let addr = env::args().nth(2).unwrap_or_else(|| {
+            eprintln!("watch expects the address of a stream\n{}", USAGE);
+            process::exit(2);
+        });
+        if let Err(err) = watch(&addr) {
+            eprintln!("couldn't watch {}: {}", addr, err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let options = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    if options.versus {
+        let mut versus = Versus::new(async_stdin(), terminal(options.cast.as_deref()), options.rules);
+        versus.set_animations(options.animations);
+        versus.start().unwrap();
+        return;
+    }
+
+    if options.coop {
+        let mut coop = Coop::new(async_stdin(), terminal(options.cast.as_deref()), options.rules);
+        coop.set_animations(options.animations);
+        coop.start().unwrap();
+        return;
+    }
+
+    if let Some(server) = &options.server {
+        let room = options.room.as_deref().unwrap_or_else(|| {
+            eprintln!("--server needs a --room to join\n{}", USAGE);
+            process::exit(2);
+        });
+        let name = player_name(options.name.clone());
+        if let Err(err) = play_room(server, room, &name, options.rules, options.animations, options.cast.as_deref()) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some((role, addr)) = options.online {
+        if let Err(err) = play_online(role, &addr, options.rules, options.animations, options.cast.as_deref()) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // the bot is started before the terminal goes into raw mode so errors print normally
+    let bot: Option<Box<dyn Bot>> = if options.ai {
+        Some(Box::new(Heuristic::default()))
+    } else if let Some(command) = options.bot {
+        match ExternalBot::launch(&command) {
+            Ok(bot) => Some(Box::new(bot)),
+            Err(err) => {
+                eprintln!("couldn't start the bot: {}", err);
+                process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let stream = options.stream.as_ref().map(|addr| Broadcast::bind(addr).unwrap_or_else(|err| {
+        eprintln!("couldn't stream on {}: {}", addr, err);
+        process::exit(1);
+    }));
+
+    let pack = options.puzzle.as_ref().map(|path| Pack::load(path).unwrap_or_else(|err| {
+        eprintln!("couldn't load the puzzle {}: {}", path, err);
+        process::exit(1);
+    }));
+
+    // every puzzle is checked up front, errors can't be printed once the game is running
+    for puzzle in pack.iter().flat_map(|pack| pack.puzzles.iter()) {
+        if let Err(err) = Engine::with_puzzle(options.rules, puzzle) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+
+    // a fumen is the board to start from, with its piece to play first if it has one
+    let (rules, seed) = (options.rules, options.seed.unwrap_or_else(rand::random));
+    let start = options.fumen.as_ref().map(|data| {
+        first_page(Some(data.clone()))
+            .and_then(|page| Engine::with_fumen(rules, seed, &page))
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            })
+    });
+
+    // racing a ghost plays its game again, with the same mode, seed and rules
+    let mode = options.mode;
+    let ghost = options.ghost.as_ref().map(|path| load_ghost(path, mode).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    }));
+
+    // everyone plays the same daily challenge, other rules can't be asked for with it
+    let challenge = options.daily.then(Challenge::today);
+
+    // a game quit partway through is offered back, unless another particular game was asked for
+    let watching = bot.is_some();
+    let saved = if options.particular || watching { None } else { offer_save() };
+
+    let mut saved_replay = None;
+    let engine = if pack.is_some() {
+        Engine::new(options.rules)
+    } else if let Some(engine) = start {
+        engine
+    } else if let Some(save) = saved {
+        saved_replay = save.replay;
+        save.engine
+    } else if let Some(challenge) = &challenge {
+        challenge.engine()
+    } else if let Some(ghost) = &ghost {
+        let replay = ghost.replay();
+        Engine::with_seed(replay.rules, replay.mode, replay.seed)
+    } else if options.mode == Mode::Dig {
+        Engine::with_dig(options.rules, options.seed.unwrap_or_else(rand::random), options.dig_rows, options.dig_target)
+    } else if let Some(seed) = options.seed {
+        Engine::with_seed(options.rules, options.mode, seed)
+    } else {
+        Engine::with_mode(options.rules, options.mode)
+    };
+    let mut game = Game::with_engine(1, 1, async_stdin(), terminal(options.cast.as_deref()), engine);
+    game.set_animations(options.animations);
+    if options.finesse || options.finesse_hints {
+        game.set_finesse(options.finesse_hints);
+    }
+    if let Some(bot) = bot {
+        game.set_bot(bot);
+    }
+    if let Some(stream) = stream {
+        game.set_stream(stream);
+    }
+    if let Some(ghost) = ghost {
+        game.set_ghost(ghost);
+    }
+    // races are always recorded, so the best run can be raced later, unless the bot ran them
+    let ranked = pack.is_none() && challenge.is_none() && !watching && matches!(game.engine().mode(), Mode::Sprint | Mode::Ultra);
+    // hints would help a run which goes on the leaderboard or could be kept as the best, marathon
+    // scores aren't kept anywhere so marathon allows them
+    game.set_hints(!ranked && challenge.is_none());
+    if let Some(replay) = saved_replay {
+        game.set_replay(replay);
+    } else if ranked || options.record.is_some() {
+        game.record();
+    }
+
+    match pack {
+        Some(pack) => {
+            if let Err(err) = play_pack(&mut game, options.rules, &pack) {
+                drop(game);
+                eprintln!("couldn't save puzzle progress: {}", err);
+            }
+        }
+        None => {
+            game.start().unwrap();
+            let replay = game.replay().cloned();
+            let name = player_name(options.name);
+            let entry = challenge.as_ref().and_then(|challenge| Entry::new(challenge, &name, game.engine()));
+            let bot_failure = game.bot_failure().map(String::from);
+
+            // quitting partway keeps the game for next time, daily challenges are played in one go
+            let kept = (game.has_quit() && !game.engine().is_over() && challenge.is_none() && !watching)
+                .then(|| Save::write(game.engine(), game.replay()));
+            drop(game);
+            if let Some(failure) = bot_failure {
+                eprintln!("{}", failure);
+            }
+            match kept {
+                Some(Ok(())) => println!("the game is saved, it's offered again next time tetrust starts"),
+                Some(Err(err)) => eprintln!("couldn't save the game: {}", err),
+                None => {}
+            }
+            if let Some(challenge) = &challenge {
+                record_daily(challenge, entry);
+            }
+            if let Some(replay) = replay {
+                save_replay(&replay, options.record.as_deref(), ranked);
+            }
+        }
+    }
+}
+
+// keeps a finished daily challenge and shows where it places
+fn record_daily(challenge: &Challenge, entry: Option<Entry>) {
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return println!("the daily challenge wasn't finished, try again before the day is out"),
+    };
+
+    let result = Leaderboard::load().and_then(|mut leaderboard| {
+        let name = entry.name.clone();
+        let best = leaderboard.record(entry);
+        leaderboard.save()?;
+        Ok((leaderboard, name, best))
+    });
+    match result {
+        Ok((leaderboard, name, best)) => {
+            if !best {
+                println!("not better than your earlier run today");
+            }
+            print_daily(challenge, &leaderboard, Some(&name));
+        }
+        Err(err) => eprintln!("couldn't save the daily result: {}", err),
+    }
+}
+
+// prints the challenge of a day and its leaderboard, after merging in other people's files
+fn daily(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut date = Date::today();
+    let mut merge = Vec::new();
+    let mut args = args;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--date" => date = args.next().as_deref().and_then(Date::parse).ok_or("--date expects a date like 2024-05-31")?,
+            "--merge" => merge.push(args.next().ok_or("--merge expects a leaderboard file")?),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let mut leaderboard = Leaderboard::load().map_err(|err| format!("couldn't load the daily leaderboard: {}", err))?;
+    if !merge.is_empty() {
+        for path in &merge {
+            let other = Leaderboard::load_file(path).map_err(|err| format!("couldn't load the leaderboard {}: {}", path, err))?;
+            println!("{} new results from {}", leaderboard.merge(other), path);
+        }
+        leaderboard.save().map_err(|err| format!("couldn't save the daily leaderboard: {}", err))?;
+    }
+
+    print_daily(&Challenge::for_date(date), &leaderboard, None);
+    Ok(())
+}
+
+fn print_daily(challenge: &Challenge, leaderboard: &Leaderboard, highlight: Option<&str>) {
+    println!("{}: {}", challenge.date, challenge.description());
+
+    let standings = leaderboard.standings(challenge.date);
+    if standings.is_empty() {
+        println!("  no results yet");
+    }
+    for (i, entry) in standings.iter().enumerate() {
+        let result = match entry.mode {
+            Mode::Ultra => format!("{} points", entry.score),
+            _ => clock(Duration::from_millis(entry.time)),
+        };
+        let marker = if Some(entry.name.as_str()) == highlight { "*" } else { " " };
+        println!("{}{:>3}. {:<16} {:>12} {:>5} pieces", marker, i + 1, entry.name, result, entry.pieces);
+    }
+}
+
+fn load_ghost(path: &str, mode: Mode) -> Result<Ghost, String> {
+    let replay = if path == "best" {
+        if !matches!(mode, Mode::Sprint | Mode::Ultra) {
+            return Err("--ghost best needs --mode sprint or ultra".to_string());
+        }
+        Replay::load_best(mode)
+            .map_err(|err| format!("couldn't load the best run: {}", err))?
+            .ok_or_else(|| format!("there's no finished {:?} run to race yet", mode).to_lowercase())?
+    } else {
+        Replay::load(path).map_err(|err| format!("couldn't load the replay {}: {}", path, err))?
+    };
+
+    // dig boards start with rows the replay doesn't have, and puzzles aren't seeded
+    if !matches!(replay.mode, Mode::Marathon | Mode::Sprint | Mode::Ultra) {
+        return Err(format!("a {:?} replay can't be raced", replay.mode).to_lowercase());
+    }
+    Ok(Ghost::new(replay))
+}
+
+// the fumen's first page, the one a game or the solver starts from
+fn first_page(data: Option<String>) -> Result<Page, String> {
+    let data = data.ok_or("--fumen expects a v115 fumen")?;
+    let pages = fumen::decode(&data).map_err(|err| format!("couldn't read the fumen: {}", err))?;
+    Ok(pages.into_iter().next().unwrap())
+}
+
+// prints a recorded game as a fumen with a page for every piece, or only the board it ended on
+fn export_fumen(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut path = None;
+    let mut last = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--last" => last = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let path = path.ok_or("fumen expects a replay file")?;
+    let replay = Replay::load(&path).map_err(|err| format!("couldn't load the replay {}: {}", path, err))?;
+    let mut pages = fumen::replay_pages(&replay)?;
+    if last {
+        pages = pages.split_off(pages.len() - 1);
+    }
+
+    println!("{}", fumen::encode(&pages));
+    Ok(())
+}
+
+// plays a recorded game again into an asciicast file
+fn render_cast(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let replay_path = args.next().ok_or("cast expects a replay file")?;
+    let cast_path = args.next().ok_or("cast expects a file to write the cast to")?;
+    if let Some(arg) = args.next() {
+        return Err(format!("unknown argument {}", arg));
+    }
+
+    let replay = Replay::load(&replay_path).map_err(|err| format!("couldn't load the replay {}: {}", replay_path, err))?;
+    cast::render(replay, Path::new(&cast_path))
+}
+
+// runs after the terminal has left raw mode so errors print normally
+fn save_replay(replay: &Replay, path: Option<&str>, ranked: bool) {
+    if let Some(path) = path {
+        if let Err(err) = replay.save(path) {
+            eprintln!("couldn't save the replay to {}: {}", path, err);
+        }
+    }
+
+    if ranked {
+        match replay.save_if_best() {
+            Ok(true) => println!("{}", format!("a new best {:?} run, race it with --ghost best", replay.mode).to_lowercase()),
+            Ok(false) => {}
+            Err(err) => eprintln!("couldn't save the best run: {}", err),
+        }
+    }
+}
+
+// asks whether to carry on with the saved game, it's only offered the once
+fn offer_save() -> Option<Save> {
+    let save = match Save::load() {
+        Ok(save) => save?,
+        Err(err) => {
+            eprintln!("couldn't load the saved game: {}", err);
+            return None;
+        }
+    };
+
+    let engine = &save.engine;
+    let mode = format!("{:?}", engine.mode()).to_lowercase();
+    print!("carry on with the {} game from last time, {} in with {} points? [Y/n] ", mode, clock(engine.played()), engine.stats().score);
+    io::stdout().flush().ok()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+
+    if let Err(err) = Save::remove() {
+        eprintln!("couldn't remove the saved game: {}", err);
+    }
+    match answer.trim() {
+        "" | "y" | "Y" | "yes" => Some(save),
+        _ => None,
+    }
+}
+
+// players go by their login name unless they give another
+fn player_name(name: Option<String>) -> String {
+    name.or_else(|| env::var("USER").ok()).unwrap_or_else(|| "player".to_string())
+}
+
+// the terminal a game is drawn on, recorded into a cast as it goes with --cast
+fn terminal(cast: Option<&str>) -> Recorder<Stdout> {
+    let cast = cast.map(|path| {
+        let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+        Cast::create(path, width, height).unwrap_or_else(|err| {
+            eprintln!("couldn't create the cast {}: {}", path, err);
+            process::exit(1);
+        })
+    });
+    Recorder::new(stdout(), cast)
+}
+
+fn watch(addr: &str) -> io::Result<()> {
+    let connection = Connection::join(addr)?;
+    let mut watch = Watch::new(async_stdin(), stdout(), connection);
+    watch.start()
+}
+
+fn play_room(server: &str, room: &str, name: &str, rules: Rules, animations: bool, cast: Option<&str>) -> io::Result<()> {
+    let connection = Connection::join(server)?;
+    let mut lobby = Lobby::new(async_stdin(), terminal(cast), connection, room, name, rules)?;
+    lobby.set_animations(animations);
+    lobby.start()
+}
+
+// connects and agrees on the first round before the terminal goes into raw mode
+fn play_online(role: Role, addr: &str, rules: Rules, animations: bool, cast: Option<&str>) -> io::Result<()> {
+    let connection = match role {
+        Role::Host => {
+            println!("waiting for a player on {}", addr);
+            Connection::host(addr)?
+        }
+        Role::Guest => Connection::join(addr)?,
+    };
+
+    let mut online = Online::new(async_stdin(), terminal(cast), connection, role, rules)?;
+    online.set_animations(animations);
+    online.start()
+}
+
+// plays the pack's puzzles in order from the first unsolved one, a failed puzzle is tried again
+fn play_pack<R: Read, W: Write>(game: &mut Game<R, W>, rules: Rules, pack: &Pack) -> io::Result<()> {
+    let mut progress = Progress::load().unwrap_or_default();
+    let mut index = progress.solved(&pack.name);
+    if index >= pack.puzzles.len() {
+        index = 0;
+    }
+
+    while index < pack.puzzles.len() {
+        game.set_engine(Engine::with_puzzle(rules, &pack.puzzles[index]).unwrap());
+        game.start()?;
+
+        if game.has_quit() {
+            break;
+        }
+        if game.engine().is_won() {
+            progress.record(&pack.name, index);
+            progress.save()?;
+            index += 1;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// a watcher which can't keep up is dropped rather than holding up the game
+const WRITE_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// How long the other side can go quiet before it counts as gone, they're expected to send
+/// something at least every `HEARTBEAT`.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+pub const HEARTBEAT: Duration = Duration::from_secs(1);
+
+/// One JSON message per line over TCP. Messages are read on their own thread so the game
+/// loop can poll for them without blocking.
+pub struct Connection<T> {
+    stream: TcpStream,
+    incoming: Receiver<io::Result<T>>,
+    last_heard: Instant,
+    last_sent: Instant,
+    closed: bool,
+    message: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned + Send + 'static> Connection<T> {
+    /// Waits for one connection on the address.
+    pub fn host<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn join<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let message = line.and_then(|line| {
+                    serde_json::from_str(&line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+                });
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    return;
+                }
+            }
+            let _ = sender.send(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the other side hung up")));
+        });
+
+        Ok(Connection {
+            stream,
+            incoming,
+            last_heard: Instant::now(),
+            last_sent: Instant::now(),
+            closed: false,
+            message: PhantomData,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// How long a send can wait on the other side before the connection is given up on.
+    pub fn set_write_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_write_timeout(Some(timeout))
+    }
+
+    /// A send which fails closes the connection, part of a line may have gone out already.
+    pub fn send<M: Serialize>(&mut self, message: &M) -> io::Result<()> {
+        if self.closed {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "the connection is closed"));
+        }
+
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.last_sent = Instant::now();
+        let result = self.stream.write_all(line.as_bytes());
+        self.closed = result.is_err();
+        result
+    }
+
+    /// Whether nothing has been sent for a while, so a heartbeat is due.
+    pub fn needs_heartbeat(&self) -> bool {
+        self.last_sent.elapsed() >= HEARTBEAT
+    }
+
+    /// The next message if one has arrived, an error once the connection is gone or has gone quiet.
+    pub fn poll(&mut self) -> io::Result<Option<T>> {
+        if self.closed {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "the connection is closed"));
+        }
+
+        let result = match self.incoming.try_recv() {
+            Ok(Ok(message)) => {
+                self.last_heard = Instant::now();
+                Ok(Some(message))
+            }
+            Ok(Err(err)) => Err(err),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the other side hung up")),
+            Err(TryRecvError::Empty) if self.last_heard.elapsed() > TIMEOUT => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "the other side stopped answering"))
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+        };
+
+        self.closed = result.is_err();
+        result
+    }
+
+    /// Waits for the next message, for handshakes before the game loop starts.
+    pub fn receive(&mut self) -> io::Result<T> {
+        loop {
+            if let Some(message) = self.poll()? {
+                return Ok(message);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+// the reading thread has its own handle on the socket, so it has to be shut to hang up
+impl<T> Drop for Connection<T> {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Sends the same JSON lines to everyone who connects, for watchers which only listen.
+pub struct Broadcast {
+    listener: TcpListener,
+    watchers: Vec<TcpStream>,
+    last_sent: Instant,
+}
+
+impl Broadcast {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcast { listener, watchers: Vec::new(), last_sent: Instant::now() })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Lets in everyone waiting to connect, returns whether anyone new did so they can be
+    /// sent what they missed.
+    pub fn accept(&mut self) -> bool {
+        let mut joined = false;
+        while let Ok((stream, _)) = self.listener.accept() {
+            let ready = stream.set_nonblocking(false)
+                .and_then(|_| stream.set_nodelay(true))
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if ready.is_ok() {
+                self.watchers.push(stream);
+                joined = true;
+            }
+        }
+        joined
+    }
+
+    pub fn watchers(&self) -> usize {
+        self.watchers.len()
+    }
+
+    /// Whether nothing has been sent for a while, watchers time out without a heartbeat.
+    pub fn needs_heartbeat(&self) -> bool {
+        self.last_sent.elapsed() >= HEARTBEAT
+    }
+
+    /// Sends the message to every watcher, any which can't be written to are dropped.
+    pub fn send<M: Serialize>(&mut self, message: &M) -> io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.last_sent = Instant::now();
+        self.watchers.retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::engine::{Engine, Input, Mode, Rules};
+    use crate::game::snapshot::Snapshot;
+
+    // keeps accepting until everyone who connected has been let in
+    fn watch(stream: &mut Broadcast, watchers: usize) -> Vec<Connection<Snapshot>> {
+        let addr = stream.local_addr().unwrap();
+        let connections = (0..watchers).map(|_| Connection::join(addr).unwrap()).collect();
+        for _ in 0..100 {
+            stream.accept();
+            if stream.watchers() == watchers {
+                return connections;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("only {} of {} watchers got in", stream.watchers(), watchers);
+    }
+
+    #[test]
+    fn every_watcher_sees_the_game() {
+        let mut stream = Broadcast::bind("127.0.0.1:0").unwrap();
+        let watchers = watch(&mut stream, 2);
+
+        let mut engine = Engine::with_seed(Rules::default(), Mode::Marathon, 3);
+        engine.input(Input::Left);
+        engine.input(Input::HardDrop);
+        stream.send(&engine.snapshot()).unwrap();
+
+        for mut watcher in watchers {
+            let snapshot = watcher.receive().unwrap();
+            assert!(snapshot == engine.snapshot());
+
+            let mut mirror = Engine::new(Rules::default());
+            mirror.mirror(&snapshot).unwrap();
+            assert_eq!(mirror.board().rows(), engine.board().rows());
+        }
+    }
+
+    #[test]
+    fn a_watcher_who_left_is_dropped() {
+        let mut stream = Broadcast::bind("127.0.0.1:0").unwrap();
+        let mut watchers = watch(&mut stream, 2);
+        drop(watchers.pop());
+
+        // the first write after hanging up can still go through
+        let snapshot = Engine::new(Rules::default()).snapshot();
+        for _ in 0..100 {
+            stream.send(&snapshot).unwrap();
+            if stream.watchers() == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(stream.watchers(), 1);
+        assert!(watchers[0].receive().unwrap() == snapshot);
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::engine::Rules;
+use crate::game::snapshot::Snapshot;
+use crate::net::Connection;
+
+// one player who isn't reading would hold up every room, so they're dropped instead
+const WRITE_TIMEOUT: Duration = Duration::from_millis(20);
+
+// how long to wait before accepting again when the process is out of file descriptors or the like
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Bumped whenever the messages change, clients with another version are turned away.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a player sends to the server, one per line.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Joins the room, creating it with these rules if nobody has yet.
+    Join { version: u32, room: String, name: String, rules: Rules },
+    /// Ready for the next round, it starts once everyone in the room is.
+    Ready,
+    Garbage { lines: usize },
+    Board { snapshot: Snapshot },
+    GameOver,
+    Ping,
+}
+
+/// What the server sends to a player.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The answer to joining, with the id the server knows the player by.
+    Joined { player: usize, room: RoomState },
+    Error { message: String },
+    /// Sent to everyone in the room whenever someone joins, leaves, readies or tops out.
+    Room { room: RoomState },
+    Start { seed: u64, rules: Rules },
+    Garbage { lines: usize, from: usize },
+    Board { player: usize, snapshot: Snapshot },
+    /// The round is over, with the last player standing if there was one.
+    RoundOver { winner: Option<usize> },
+    Ping,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoomState {
+    pub name: String,
+    pub players: Vec<PlayerState>,
+    pub playing: bool,
+    pub rounds: Vec<RoundResult>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub id: usize,
+    pub name: String,
+    pub wins: usize,
+    pub ready: bool,
+
+    /// Still playing in the current round.
+    pub alive: bool,
+}
+
+/// How a round went, kept by the server for as long as it runs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoundResult {
+    pub seed: u64,
+    pub winner: Option<String>,
+
+    /// Everyone who played, from the winner down to the first to top out.
+    pub placings: Vec<String>,
+}
+
+struct Client {
+    id: usize,
+    connection: Connection<ClientMessage>,
+    room: Option<String>,
+}
+
+struct Room {
+    state: RoomState,
+    rules: Rules,
+    seed: u64,
+
+    // who topped out this round, in order
+    out: Vec<usize>,
+
+    // who each player's garbage went to last, attacks go round the other players in turn
+    targets: BTreeMap<usize, usize>,
+}
+
+/// Keeps the rooms and passes garbage and boards between the players in them.
+pub struct Server {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    rooms: BTreeMap<String, Room>,
+    next_id: usize,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server { listener, clients: Vec::new(), rooms: BTreeMap::new(), next_id: 1 })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves until the process is stopped.
+    pub fn run(&mut self) {
+        loop {
+            self.accept();
+            self.receive();
+            self.heartbeat();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // a client which can't be set up is dropped, it doesn't stop the server
+                    let connection = stream.set_nonblocking(false)
+                        .and_then(|_| Connection::new(stream))
+                        .and_then(|connection| connection.set_write_timeout(WRITE_TIMEOUT).map(|_| connection));
+                    if let Ok(connection) = connection {
+                        self.clients.push(Client { id: self.next_id, connection, room: None });
+                        self.next_id += 1;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+
+                // a client which hung up during the handshake only affects itself
+                Err(err) if matches!(err.kind(), io::ErrorKind::Interrupted | io::ErrorKind::ConnectionAborted | io::ErrorKind::ConnectionReset) => {
+                    eprintln!("couldn't accept a client: {}", err);
+                }
+
+                // most likely out of file descriptors, the rooms carry on while some are freed
+                Err(err) => {
+                    eprintln!("couldn't accept clients for now: {}", err);
+                    thread::sleep(ACCEPT_BACKOFF);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut i = 0;
+        while i < self.clients.len() {
+            match self.clients[i].connection.poll() {
+                Ok(Some(message)) => {
+                    let id = self.clients[i].id;
+                    self.handle(id, message);
+                }
+                Ok(None) => i += 1,
+                Err(_) => {
+                    let client = self.clients.remove(i);
+                    self.leave(client.id, client.room);
+                }
+            }
+        }
+    }
+
+    fn heartbeat(&mut self) {
+        for client in self.clients.iter_mut() {
+            if client.connection.needs_heartbeat() {
+                let _ = client.connection.send(&ServerMessage::Ping);
+            }
+        }
+    }
+
+    fn handle(&mut self, id: usize, message: ClientMessage) {
+        let room = self.clients.iter().find(|client| client.id == id).and_then(|client| client.room.clone());
+        match (message, room) {
+            (ClientMessage::Join { version, room, name, rules }, None) => self.join(id, version, room, name, rules),
+            (ClientMessage::Join { .. }, Some(_)) => self.send(id, &ServerMessage::Error { message: "already in a room".to_string() }),
+            (ClientMessage::Ready, Some(room)) => self.ready(id, &room),
+            (ClientMessage::Garbage { lines }, Some(room)) => self.garbage(id, &room, lines),
+            (ClientMessage::Board { snapshot }, Some(room)) => {
+                if self.is_alive(id, &room) {
+                    self.broadcast(&room, Some(id), &ServerMessage::Board { player: id, snapshot });
+                }
+            }
+            (ClientMessage::GameOver, Some(room)) => self.top_out(id, &room),
+            (ClientMessage::Ping, _) => {}
+            (_, None) => self.send(id, &ServerMessage::Error { message: "join a room first".to_string() }),
+        }
+    }
+
+    fn join(&mut self, id: usize, version: u32, name: String, player: String, rules: Rules) {
+        if version != PROTOCOL_VERSION {
+            let message = format!("the server speaks version {}, this is {}", PROTOCOL_VERSION, version);
+            return self.send(id, &ServerMessage::Error { message });
+        }
+
+        let room = self.rooms.entry(name.clone()).or_insert_with(|| Room {
+            state: RoomState { name: name.clone(), players: Vec::new(), playing: false, rounds: Vec::new() },
+            rules,
+            seed: 0,
+            out: Vec::new(),
+            targets: BTreeMap::new(),
+        });
+        room.state.players.push(PlayerState { id, name: player, wins: 0, ready: false, alive: false });
+        let state = room.state.clone();
+
+        if let Some(client) = self.clients.iter_mut().find(|client| client.id == id) {
+            client.room = Some(name.clone());
+        }
+        // someone who joins mid-round watches the boards of the rest of it
+        self.send(id, &ServerMessage::Joined { player: id, room: state });
+        self.broadcast_room(&name);
+    }
+
+    fn ready(&mut self, id: usize, name: &str) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            if let Some(player) = room.state.players.iter_mut().find(|player| player.id == id) {
+                player.ready = !room.state.playing;
+            }
+        }
+        self.try_start(name);
+        self.broadcast_room(name);
+    }
+
+    // a round starts once there are two or more players and all of them are ready
+    fn try_start(&mut self, name: &str) {
+        let room = match self.rooms.get_mut(name) {
+            Some(room) if !room.state.playing => room,
+            _ => return,
+        };
+
+        let players = &mut room.state.players;
+        if players.len() >= 2 && players.iter().all(|player| player.ready) {
+            room.seed = rand::random();
+            room.state.playing = true;
+            room.out.clear();
+            room.targets.clear();
+            for player in players.iter_mut() {
+                player.ready = false;
+                player.alive = true;
+            }
+
+            let start = ServerMessage::Start { seed: room.seed, rules: room.rules };
+            self.broadcast(name, None, &start);
+        }
+    }
+
+    fn garbage(&mut self, id: usize, name: &str, lines: usize) {
+        if !self.is_alive(id, name) {
+            return;
+        }
+        let room = match self.rooms.get_mut(name) {
+            Some(room) => room,
+            None => return,
+        };
+
+        // the next player standing after the one attacked last time
+        let alive: Vec<usize> = room.state.players.iter()
+            .filter(|player| player.alive && player.id != id)
+            .map(|player| player.id)
+            .collect();
+        let last = room.targets.get(&id).copied().unwrap_or(0);
+        let target = match alive.iter().find(|other| **other > last).or_else(|| alive.first()) {
+            Some(target) => *target,
+            None => return,
+        };
+        room.targets.insert(id, target);
+
+        self.send(target, &ServerMessage::Garbage { lines, from: id });
+    }
+
+    fn top_out(&mut self, id: usize, name: &str) {
+        if !self.is_alive(id, name) {
+            return;
+        }
+        if let Some(room) = self.rooms.get_mut(name) {
+            if let Some(player) = room.state.players.iter_mut().find(|player| player.id == id) {
+                player.alive = false;
+            }
+            room.out.push(id);
+        }
+        self.check_round_over(name);
+        self.broadcast_room(name);
+    }
+
+    fn leave(&mut self, id: usize, name: Option<String>) {
+        let name = match name {
+            Some(name) => name,
+            None => return,
+        };
+
+        // leaving mid-round is the same as topping out
+        self.top_out(id, &name);
+        if let Some(room) = self.rooms.get_mut(&name) {
+            room.state.players.retain(|player| player.id != id);
+
+            // an empty room is gone, naming it again makes a new one with new rules
+            if room.state.players.is_empty() {
+                self.rooms.remove(&name);
+                return;
+            }
+        }
+        self.try_start(&name);
+        self.broadcast_room(&name);
+    }
+
+    fn check_round_over(&mut self, name: &str) {
+        let room = match self.rooms.get_mut(name) {
+            Some(room) if room.state.playing => room,
+            _ => return,
+        };
+        let alive: Vec<usize> = room.state.players.iter().filter(|player| player.alive).map(|player| player.id).collect();
+        if alive.len() > 1 {
+            return;
+        }
+
+        let winner = alive.first().copied();
+        let name_of = |id: &usize| room.state.players.iter()
+            .find(|player| player.id == *id)
+            .map(|player| player.name.clone());
+        let placings = winner.iter().chain(room.out.iter().rev()).filter_map(name_of).collect();
+        room.state.rounds.push(RoundResult { seed: room.seed, winner: winner.as_ref().and_then(name_of), placings });
+
+        room.state.playing = false;
+        for player in room.state.players.iter_mut() {
+            if Some(player.id) == winner {
+                player.wins += 1;
+            }
+            player.alive = false;
+        }
+
+        self.broadcast(name, None, &ServerMessage::RoundOver { winner });
+    }
+
+    fn is_alive(&self, id: usize, name: &str) -> bool {
+        self.rooms.get(name)
+            .filter(|room| room.state.playing)
+            .is_some_and(|room| room.state.players.iter().any(|player| player.id == id && player.alive))
+    }
+
+    fn broadcast_room(&mut self, name: &str) {
+        if let Some(room) = self.rooms.get(name) {
+            let message = ServerMessage::Room { room: room.state.clone() };
+            self.broadcast(name, None, &message);
+        }
+    }
+
+    // sends to everyone in the room, except the player who it came from
+    fn broadcast(&mut self, name: &str, except: Option<usize>, message: &ServerMessage) {
+        for client in self.clients.iter_mut() {
+            if client.room.as_deref() == Some(name) && Some(client.id) != except {
+                let _ = client.connection.send(message);
+            }
+        }
+    }
+
+    fn send(&mut self, id: usize, message: &ServerMessage) {
+        if let Some(client) = self.clients.iter_mut().find(|client| client.id == id) {
+            let _ = client.connection.send(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(addr: SocketAddr, name: &str, rules: Rules) -> Connection<ServerMessage> {
+        let mut connection = Connection::join(addr).unwrap();
+        let join = ClientMessage::Join { version: PROTOCOL_VERSION, room: "friday".to_string(), name: name.to_string(), rules };
+        connection.send(&join).unwrap();
+        match connection.receive().unwrap() {
+            ServerMessage::Joined { .. } => connection,
+            _ => panic!("expected to join the room"),
+        }
+    }
+
+    // skips the room updates to get to the next message which is anything else
+    fn next(connection: &mut Connection<ServerMessage>) -> ServerMessage {
+        loop {
+            match connection.receive().unwrap() {
+                ServerMessage::Room { .. } | ServerMessage::Ping => {}
+                message => return message,
+            }
+        }
+    }
+
+    #[test]
+    fn an_empty_room_is_made_again_with_new_rules() {
+        let mut server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let first = Rules { lock_delay: Duration::from_millis(100), ..Rules::default() };
+        drop(join(addr, "ann", first));
+        thread::sleep(Duration::from_millis(200));
+
+        let second = Rules { lock_delay: Duration::from_millis(900), ..Rules::default() };
+        let mut bob = join(addr, "bob", second);
+        let mut cat = join(addr, "cat", Rules::default());
+        bob.send(&ClientMessage::Ready).unwrap();
+        cat.send(&ClientMessage::Ready).unwrap();
+
+        match next(&mut cat) {
+            ServerMessage::Start { rules, .. } => assert_eq!(rules.lock_delay, second.lock_delay),
+            _ => panic!("expected the round to start"),
+        }
+    }
+}
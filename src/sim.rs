@@ -0,0 +1,312 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::bot::Bot;
+use crate::game::engine::{Engine, Mode, Rules};
+
+/// How one headless game went.
+#[derive(Clone, Serialize)]
+pub struct Outcome {
+    pub seed: u64,
+    pub pieces: usize,
+    pub lines: usize,
+    pub score: usize,
+    pub topped_out: bool,
+
+    /// Wall clock time the game took to play, the bot's thinking is most of it.
+    pub seconds: f64,
+}
+
+impl Outcome {
+    pub fn pieces_per_second(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.pieces as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The rules without any of the delays, which only matter to someone watching.
+pub fn headless(rules: Rules) -> Rules {
+    let zero = Duration::from_millis(0);
+    Rules { line_clear_delay: zero, lock_flash: zero, entry_delay: zero, ..rules }
+}
+
+/// Plays a whole game with the bot as fast as it can think, until it tops out or has placed
+/// max_pieces. A bot with nothing to suggest counts as topped out, a bot which failed is an error.
+pub fn play(bot: &mut dyn Bot, rules: Rules, seed: u64, max_pieces: usize) -> Result<Outcome, String> {
+    let start = Instant::now();
+    let mut engine = Engine::with_seed(rules, Mode::Marathon, seed);
+    let mut stuck = false;
+
+    while !engine.is_lost() && engine.stats().pieces < max_pieces {
+        if !play_piece(bot, &mut engine, rules) {
+            if let Some(failure) = bot.failure() {
+                return Err(failure.to_string());
+            }
+            stuck = true;
+            break;
+        }
+    }
+
+    let stats = engine.stats();
+    Ok(Outcome {
+        seed,
+        pieces: stats.pieces,
+        lines: stats.lines,
+        score: stats.score,
+        topped_out: stuck || engine.is_lost(),
+        seconds: start.elapsed().as_secs_f64(),
+    })
+}
+
+// the bot places one piece, false when it had nothing to suggest
+fn play_piece(bot: &mut dyn Bot, engine: &mut Engine, rules: Rules) -> bool {
+    // only happens with delays left in the rules, wait them out
+    while engine.active().is_none() && !engine.is_over() {
+        engine.tick(rules.fall_rate);
+    }
+    if engine.is_over() {
+        return true;
+    }
+
+    match bot.suggest(engine) {
+        Some(placement) => {
+            for input in placement.inputs {
+                engine.input(input);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// How one headless versus game between two bots went.
+#[derive(Clone, Serialize)]
+pub struct Duel {
+    pub seed: u64,
+
+    /// 0 or 1, none when both topped out on the same turn or neither did before max_pieces.
+    pub winner: Option<usize>,
+    pub pieces: [usize; 2],
+
+    /// Garbage sent to the other side, after cancelling what was coming in.
+    pub attack: [usize; 2],
+    pub seconds: f64,
+}
+
+/// Plays two bots against each other with the same pieces, taking turns a piece at a time and
+/// sending garbage after every turn, until someone tops out or both have placed max_pieces.
+/// A bot which failed doesn't lose, the duel is an error.
+pub fn duel(mut bots: [&mut dyn Bot; 2], rules: Rules, seed: u64, max_pieces: usize) -> Result<Duel, String> {
+    let start = Instant::now();
+    let mut engines = [Engine::with_seed(rules, Mode::Marathon, seed), Engine::with_seed(rules, Mode::Marathon, seed)];
+    let mut out = [false; 2];
+    let mut attack = [0; 2];
+
+    while out == [false; 2] && engines.iter().any(|engine| engine.stats().pieces < max_pieces) {
+        for (i, bot) in bots.iter_mut().enumerate() {
+            let engine = &mut engines[i];
+            if engine.stats().pieces < max_pieces && !play_piece(*bot, engine, rules) {
+                if let Some(failure) = bot.failure() {
+                    return Err(failure.to_string());
+                }
+                out[i] = true;
+            }
+            out[i] |= engine.is_lost();
+        }
+
+        let sent = [engines[0].take_attack(), engines[1].take_attack()];
+        for i in 0..2 {
+            attack[i] += sent[i];
+            engines[1 - i].receive_garbage(sent[i]);
+        }
+    }
+
+    Ok(Duel {
+        seed,
+        winner: match out {
+            [false, true] => Some(0),
+            [true, false] => Some(1),
+            _ => None,
+        },
+        pieces: [engines[0].stats().pieces, engines[1].stats().pieces],
+        attack,
+        seconds: start.elapsed().as_secs_f64(),
+    })
+}
+
+/// One bot's results over every duel it played in a tournament.
+#[derive(Serialize)]
+pub struct Standing {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    pub win_rate: f64,
+    pub attack_per_piece: f64,
+    pub mean_pieces: f64,
+}
+
+impl Standing {
+    /// The standings of every bot, `duels` says which two bots played each one.
+    pub fn table(names: &[String], duels: &[([usize; 2], Duel)]) -> Vec<Standing> {
+        let mut table: Vec<Standing> = names.iter()
+            .map(|name| Standing { name: name.clone(), games: 0, wins: 0, losses: 0, draws: 0, win_rate: 0.0, attack_per_piece: 0.0, mean_pieces: 0.0 })
+            .collect();
+        let mut attack = vec![0; names.len()];
+        let mut pieces = vec![0; names.len()];
+
+        for (players, duel) in duels {
+            for (side, &bot) in players.iter().enumerate() {
+                let standing = &mut table[bot];
+                standing.games += 1;
+                match duel.winner {
+                    Some(winner) if winner == side => standing.wins += 1,
+                    Some(_) => standing.losses += 1,
+                    None => standing.draws += 1,
+                }
+                attack[bot] += duel.attack[side];
+                pieces[bot] += duel.pieces[side];
+            }
+        }
+
+        for (i, standing) in table.iter_mut().enumerate() {
+            if standing.games > 0 {
+                standing.win_rate = standing.wins as f64 / standing.games as f64;
+                standing.mean_pieces = pieces[i] as f64 / standing.games as f64;
+            }
+            if pieces[i] > 0 {
+                standing.attack_per_piece = attack[i] as f64 / pieces[i] as f64;
+            }
+        }
+
+        table
+    }
+}
+
+/// Aggregate statistics over a batch of games.
+#[derive(Serialize)]
+pub struct Summary {
+    pub games: usize,
+    pub mean_pieces: f64,
+    pub mean_lines: f64,
+    pub mean_score: f64,
+    pub min_score: usize,
+    pub p25_score: usize,
+    pub median_score: usize,
+    pub p75_score: usize,
+    pub max_score: usize,
+    pub top_out_rate: f64,
+    pub pieces_per_second: f64,
+}
+
+impl Summary {
+    pub fn new(outcomes: &[Outcome]) -> Self {
+        let games = outcomes.len();
+        let mean = |value: &dyn Fn(&Outcome) -> f64| {
+            if games == 0 {
+                0.0
+            } else {
+                outcomes.iter().map(value).sum::<f64>() / games as f64
+            }
+        };
+
+        let mut scores: Vec<usize> = outcomes.iter().map(|outcome| outcome.score).collect();
+        scores.sort_unstable();
+        let percentile = |p: usize| {
+            if scores.is_empty() {
+                0
+            } else {
+                scores[(scores.len() - 1) * p / 100]
+            }
+        };
+
+        // pieces per second over all the games, rather than the mean of each game's rate
+        let pieces: usize = outcomes.iter().map(|outcome| outcome.pieces).sum();
+        let seconds: f64 = outcomes.iter().map(|outcome| outcome.seconds).sum();
+
+        Summary {
+            games,
+            mean_pieces: mean(&|outcome| outcome.pieces as f64),
+            mean_lines: mean(&|outcome| outcome.lines as f64),
+            mean_score: mean(&|outcome| outcome.score as f64),
+            min_score: percentile(0),
+            p25_score: percentile(25),
+            median_score: percentile(50),
+            p75_score: percentile(75),
+            max_score: percentile(100),
+            top_out_rate: mean(&|outcome| if outcome.topped_out { 1.0 } else { 0.0 }),
+            pieces_per_second: if seconds > 0.0 { pieces as f64 / seconds } else { 0.0 },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{Heuristic, Placement};
+
+    // suggests a few pieces then gives up, failing if it has a reason
+    struct Quitter {
+        pieces: usize,
+        failure: Option<&'static str>,
+    }
+
+    impl Bot for Quitter {
+        fn suggest(&mut self, engine: &Engine) -> Option<Placement> {
+            if self.pieces == 0 {
+                return None;
+            }
+            self.pieces -= 1;
+            Heuristic::default().suggest(engine)
+        }
+
+        fn failure(&self) -> Option<&str> {
+            self.failure.filter(|_| self.pieces == 0)
+        }
+    }
+
+    fn summary(outcome: &Outcome) -> (usize, usize, usize, bool) {
+        (outcome.pieces, outcome.lines, outcome.score, outcome.topped_out)
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_game() {
+        let rules = headless(Rules::default());
+        let first = play(&mut Heuristic::default(), rules, 7, 60).unwrap();
+        let again = play(&mut Heuristic::default(), rules, 7, 60).unwrap();
+
+        assert_eq!(summary(&first), summary(&again));
+        assert_eq!((first.pieces, first.topped_out), (60, false));
+        assert!(first.lines > 0);
+    }
+
+    #[test]
+    fn a_bot_with_nothing_to_suggest_has_topped_out() {
+        let outcome = play(&mut Quitter { pieces: 5, failure: None }, headless(Rules::default()), 1, 60).unwrap();
+        assert_eq!((outcome.pieces, outcome.topped_out), (5, true));
+    }
+
+    #[test]
+    fn a_bot_which_failed_is_an_error() {
+        let rules = headless(Rules::default());
+        let result = play(&mut Quitter { pieces: 5, failure: Some("bot exited") }, rules, 1, 60);
+        assert_eq!(result.err().as_deref(), Some("bot exited"));
+
+        let mut quitter = Quitter { pieces: 5, failure: Some("bot exited") };
+        let result = duel([&mut Heuristic::default(), &mut quitter], rules, 1, 60);
+        assert_eq!(result.err().as_deref(), Some("bot exited"));
+    }
+
+    #[test]
+    fn a_bot_which_runs_out_of_moves_loses_the_duel() {
+        let mut quitter = Quitter { pieces: 5, failure: None };
+        let duel = duel([&mut quitter, &mut Heuristic::default()], headless(Rules::default()), 1, 60).unwrap();
+        assert_eq!(duel.winner, Some(1));
+        assert_eq!(duel.pieces, [5, 6]);
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Where progress and other things worth keeping between games are stored, `~/.tetrust`
+/// unless `TETRUST_HOME` says otherwise.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("TETRUST_HOME") {
+        return PathBuf::from(dir);
+    }
+
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".tetrust")
+}
+
+/// Reads a JSON file from the data directory, a file which isn't there yet is the default.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> io::Result<T> {
+    match fs::read_to_string(data_dir().join(name)) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn save<T: Serialize>(name: &str, value: &T) -> io::Result<()> {
+    let dir = data_dir();
+    fs::create_dir_all(&dir)?;
+
+    // written to the side first so quitting halfway doesn't leave half a file
+    let path = dir.join(name);
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, serde_json::to_string_pretty(value)?)?;
+    fs::rename(temp, path)
+}
+
+/// Deletes a file from the data directory, it's fine if it was never there.
+pub fn remove(name: &str) -> io::Result<()> {
+    match fs::remove_file(data_dir().join(name)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}